@@ -0,0 +1,149 @@
+//! Batches mixing payload types, split back into typed results per request.
+//!
+//! ```no_run
+//! # use algolia_recommend_rs::{RecommendClient, RecommendRequest};
+//! # use algolia_recommend_rs::batch::RecommendBatch;
+//! # #[derive(serde::Deserialize)] struct Product {}
+//! # #[derive(serde::Deserialize)] struct Category {}
+//! # async fn run(client: RecommendClient) -> algolia_recommend_rs::error::Result<()> {
+//! let mut batch = RecommendBatch::new();
+//! let products = batch.add::<Product>(RecommendRequest::bought_together("products", "obj-1"));
+//! let categories = batch.add::<Category>(RecommendRequest::trending_items("categories"));
+//!
+//! let response = client.get_recommendations_batch(batch).await?;
+//! let products = response.get(&products)?;
+//! let categories = response.get(&categories)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::models::{RecommendRequest, RecommendResult};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Ids of the batches created so far. 0 is left for responses that didn't
+// come from a batch, e.g. deserialized ones.
+static NEXT_BATCH_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Requests sent together in one call, each with its own payload type.
+#[derive(Debug, Clone)]
+pub struct RecommendBatch {
+    pub(crate) id: u64,
+    pub(crate) requests: Vec<RecommendRequest>,
+}
+
+impl Default for RecommendBatch {
+    fn default() -> Self {
+        Self {
+            id: NEXT_BATCH_ID.fetch_add(1, Ordering::Relaxed),
+            requests: Vec::new(),
+        }
+    }
+}
+
+impl RecommendBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `request`, whose hits will be read as `T`.
+    pub fn add<T: DeserializeOwned>(&mut self, request: RecommendRequest) -> BatchHandle<T> {
+        self.requests.push(request);
+        BatchHandle {
+            batch: self.id,
+            index: self.requests.len() - 1,
+            payload: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+/// Identifies one request of a [`RecommendBatch`] and the type of its hits.
+/// It only reads the response to that batch (or to a clone of it).
+pub struct BatchHandle<T> {
+    batch: u64,
+    index: usize,
+    // fn() -> T keeps the handle Send, Sync and Copy whatever T is
+    payload: PhantomData<fn() -> T>,
+}
+
+impl<T> BatchHandle<T> {
+    /// Position of the request in its batch.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Clone for BatchHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BatchHandle<T> {}
+
+impl<T> fmt::Debug for BatchHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchHandle")
+            .field("batch", &self.batch)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Untyped results of a [`RecommendBatch`], typed on access.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchResponse {
+    // Id of the batch sent
+    #[serde(skip)]
+    pub(crate) batch: u64,
+    pub(crate) results: Vec<Value>,
+    // Whether each result was served stale from the cache, empty without one
+    #[serde(skip)]
+    pub(crate) stale: Vec<bool>,
+}
+
+impl BatchResponse {
+    /// The result of the request behind `handle`. Fails on its own if its
+    /// hits don't match `T`, without affecting the other requests of the
+    /// batch, and with [`Error::ForeignBatchHandle`] if `handle` was made
+    /// by another batch.
+    pub fn get<T: DeserializeOwned>(&self, handle: &BatchHandle<T>) -> Result<RecommendResult<T>> {
+        if handle.batch != self.batch {
+            return Err(Error::ForeignBatchHandle {
+                index: handle.index,
+            });
+        }
+        let value = self.results.get(handle.index).ok_or(Error::MissingResult {
+            index: handle.index,
+        })?;
+        let mut result = RecommendResult::<T>::deserialize(value)?;
+        result.stale = self.stale.get(handle.index).copied().unwrap_or(false);
+        Ok(result)
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The raw JSON results, in request order.
+    pub fn raw(&self) -> &[Value] {
+        &self.results
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::{check_result_count, Error, Result};
+use crate::models::{RecommendResponse, RecommendResult};
+use std::ops::Range;
+
+/// How [`RecommendClient::get_recommendations_bulk`](crate::RecommendClient::get_recommendations_bulk)
+/// splits and runs a large list of requests.
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    /// Requests sent per HTTP call.
+    pub chunk_size: usize,
+    /// Calls in flight at the same time. They are spread over the hosts by
+    /// the usual host rotation.
+    pub concurrency: usize,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 50,
+            concurrency: 4,
+        }
+    }
+}
+
+impl BulkOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+/// A chunk whose call failed, after retries.
+#[derive(Debug)]
+pub struct ChunkFailure {
+    /// Position of the chunk, starting at 0.
+    pub chunk: usize,
+    /// Positions of its requests in the input.
+    pub requests: Range<usize>,
+    /// An [`Error::MissingResult`] here holds a position in the input, within
+    /// `requests`.
+    pub error: Error,
+}
+
+/// Results of a bulk call, in input order.
+#[derive(Debug)]
+pub struct BulkResponse<T> {
+    /// One entry per input request, `None` when its chunk failed.
+    pub results: Vec<Option<RecommendResult<T>>>,
+    pub failures: Vec<ChunkFailure>,
+}
+
+impl<T> BulkResponse<T> {
+    pub(crate) fn from_chunks(
+        total: usize,
+        chunk_size: usize,
+        outcomes: Vec<Result<RecommendResponse<T>>>,
+    ) -> Self {
+        let mut results = Vec::with_capacity(total);
+        let mut failures = Vec::new();
+        for (chunk, outcome) in outcomes.into_iter().enumerate() {
+            let start = chunk * chunk_size;
+            let requests = start..total.min(start + chunk_size);
+            // A short or long answer would misalign every later result
+            let outcome = outcome
+                .and_then(|response| {
+                    check_result_count(requests.len(), response.results.len())?;
+                    Ok(response)
+                })
+                // Chunk positions, from the check above or from the cache
+                // path, become input positions
+                .map_err(|error| match error {
+                    Error::MissingResult { index } => Error::MissingResult {
+                        index: start + index,
+                    },
+                    error => error,
+                });
+            match outcome {
+                Ok(response) => results.extend(response.results.into_iter().map(Some)),
+                Err(error) => {
+                    results.extend(requests.clone().map(|_| None));
+                    failures.push(ChunkFailure {
+                        chunk,
+                        requests,
+                        error,
+                    });
+                }
+            }
+        }
+        Self { results, failures }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The merged response, or [`Error::BulkFailed`] if any chunk failed.
+    pub fn into_response(self) -> Result<RecommendResponse<T>> {
+        if !self.failures.is_empty() {
+            return Err(Error::BulkFailed {
+                failures: self.failures,
+            });
+        }
+        Ok(RecommendResponse {
+            results: self.results.into_iter().flatten().collect(),
+        })
+    }
+}
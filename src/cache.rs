@@ -0,0 +1,482 @@
+use crate::error::Result;
+use futures_util::future::{join_all, BoxFuture};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Storage for cached recommendations: serialized `RecommendResult`s under
+/// keys built by the client.
+///
+/// Failures are not fatal: a `get` that fails or returns an unreadable entry
+/// counts as a miss and a failed `set` is dropped, all are counted in
+/// [`CacheStats::errors`].
+pub trait RecommendCache: Debug + Send + Sync {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>>;
+
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>>;
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// What is stored under each of `keys`, in order. This is how the client
+    /// looks up the requests of a call; backends that can do it in one round
+    /// trip should. A failure counts as one error and a miss for every key.
+    fn get_many<'a>(&'a self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>> {
+        Box::pin(async move {
+            join_all(keys.iter().map(|key| self.get(key)))
+                .await
+                .into_iter()
+                .collect()
+        })
+    }
+
+    /// Like [`RecommendCache::get_many`], for storing the results of a call.
+    fn set_many<'a>(
+        &'a self,
+        entries: Vec<(&'a str, Vec<u8>)>,
+        ttl: Duration,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            join_all(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| self.set(key, value, ttl)),
+            )
+            .await
+            .into_iter()
+            .collect()
+        })
+    }
+
+    /// Drop every entry. Backends shared with other data may leave this out.
+    fn clear(&self) {}
+
+    /// Fills `evictions`, `entries` and `bytes` when the backend knows them.
+    fn usage(&self) -> CacheStats {
+        CacheStats::default()
+    }
+}
+
+/// TTL and limits of the in-memory cache, see
+/// [`RecommendClientBuilder::cache`](crate::RecommendClientBuilder::cache).
+///
+/// Once either limit is reached the least recently used entries are evicted.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// How long a result is served from the cache.
+    pub ttl: Duration,
+    pub max_entries: usize,
+    /// Total size of the cached keys and serialized results.
+    pub max_bytes: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(60),
+            max_entries: 10_000,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+/// Counters of the recommendations cache, since the client was built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Requests answered from the cache.
+    pub hits: u64,
+    /// Requests that had to be sent, including bypassed ones.
+    pub misses: u64,
+    /// Results served past their TTL, see
+    /// [`RecommendClientBuilder::stale_while_revalidate`](crate::RecommendClientBuilder::stale_while_revalidate)
+    /// and [`RecommendClientBuilder::stale_if_error`](crate::RecommendClientBuilder::stale_if_error).
+    pub stale: u64,
+    /// Backend calls that failed.
+    pub errors: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Instant,
+    // Position in `Lru::order`
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, Entry>,
+    // Least recently used first
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    bytes: usize,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.tick);
+            entry.tick = tick;
+            self.order.insert(tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.bytes -= key.len() + entry.value.len();
+        Some(entry)
+    }
+
+    fn pop_oldest(&mut self) -> bool {
+        let Some((_, key)) = self.order.pop_first() else {
+            return false;
+        };
+        if let Some(entry) = self.entries.remove(&key) {
+            self.bytes -= key.len() + entry.value.len();
+        }
+        true
+    }
+}
+
+/// In-memory TTL + LRU [`RecommendCache`], the backend of
+/// [`RecommendClientBuilder::cache`](crate::RecommendClientBuilder::cache).
+#[derive(Debug)]
+pub struct MemoryCache {
+    config: CacheConfig,
+    lru: Mutex<Lru>,
+    evictions: AtomicU64,
+}
+
+impl MemoryCache {
+    /// `config.ttl` is unused here, the client passes the TTL of each entry.
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            lru: Mutex::new(Lru::default()),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn get_fresh(&self, key: &str) -> Option<Vec<u8>> {
+        let mut lru = self.lock();
+        match lru.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                let value = entry.value.clone();
+                lru.touch(key);
+                Some(value)
+            }
+            Some(_) => {
+                lru.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let size = key.len() + value.len();
+        // Would evict everything else and still not fit
+        if size > self.config.max_bytes || self.config.max_entries == 0 {
+            return;
+        }
+
+        let mut lru = self.lock();
+        lru.remove(key);
+        let mut evicted = 0;
+        while lru.entries.len() >= self.config.max_entries
+            || lru.bytes + size > self.config.max_bytes
+        {
+            if !lru.pop_oldest() {
+                break;
+            }
+            evicted += 1;
+        }
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+
+        let tick = lru.next_tick;
+        lru.next_tick += 1;
+        lru.bytes += size;
+        lru.order.insert(tick, key.to_string());
+        lru.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires_at: Instant::now() + ttl,
+                tick,
+            },
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        // Every mutation leaves the maps consistent, so a poisoned lock is fine
+        self.lru.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RecommendCache for MemoryCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        let value = self.get_fresh(key);
+        Box::pin(async move { Ok(value) })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>> {
+        self.insert(key, value, ttl);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.lock().remove(key);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    fn usage(&self) -> CacheStats {
+        let lru = self.lock();
+        CacheStats {
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: lru.entries.len(),
+            bytes: lru.bytes,
+            ..CacheStats::default()
+        }
+    }
+}
+
+/// What the cache holds for one request.
+pub(crate) enum Lookup {
+    Fresh(Value),
+    /// Past its TTL but still within a stale window. `revalidate` is set
+    /// when it may be served right away while being refreshed; otherwise
+    /// it is only a fallback for a failed call.
+    Stale {
+        value: Value,
+        revalidate: bool,
+    },
+    Miss,
+}
+
+/// The client side of caching: key scheme, TTL and hit/miss counters, over
+/// whichever backend is configured.
+#[derive(Debug)]
+pub(crate) struct CacheLayer {
+    backend: Arc<dyn RecommendCache>,
+    ttl: Duration,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+    // For all the lookups, or all the stores, of one call
+    timeout: Duration,
+    namespace: String,
+    // Keys with a background refresh in flight
+    refreshing: Mutex<HashSet<String>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stale: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl CacheLayer {
+    pub(crate) fn new(
+        backend: Arc<dyn RecommendCache>,
+        ttl: Duration,
+        stale_while_revalidate: Duration,
+        stale_if_error: Duration,
+        timeout: Duration,
+        app_id: &str,
+        api_key: &str,
+    ) -> Self {
+        Self {
+            backend,
+            ttl,
+            stale_while_revalidate,
+            stale_if_error,
+            timeout,
+            // Keeps applications apart in a backend shared between them, and
+            // API keys too since their restrictions can change the results
+            namespace: format!("{app_id}:{}:", digest(api_key.as_bytes())),
+            refreshing: Mutex::new(HashSet::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            stale: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn key(&self, canonical_request: &str) -> String {
+        format!("{}{canonical_request}", self.namespace)
+    }
+
+    /// What is cached under each of `keys`, counting hits and misses. An
+    /// entry served while it is revalidated counts as a hit. When the lookup
+    /// fails or is still running at the cache timeout, every key is a miss.
+    pub(crate) async fn get_all(&self, keys: &[String]) -> Vec<Lookup> {
+        let stored = match tokio::time::timeout(self.timeout, self.backend.get_many(keys)).await {
+            Ok(Ok(stored)) if stored.len() == keys.len() => stored,
+            _ => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                vec![None; keys.len()]
+            }
+        };
+        stored
+            .iter()
+            .map(|stored| self.lookup(stored.as_deref()))
+            .collect()
+    }
+
+    // An entry that can't be read, e.g. written by another version or
+    // truncated, is a miss and counts as an error
+    fn lookup(&self, stored: Option<&[u8]>) -> Lookup {
+        let opened = stored.map(|stored| {
+            let (fresh_until, value) = open_envelope(stored)?;
+            Some((fresh_until, serde_json::from_slice::<Value>(value).ok()?))
+        });
+        let lookup = match opened {
+            None => Lookup::Miss,
+            Some(None) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                Lookup::Miss
+            }
+            Some(Some((fresh_until, value))) => {
+                let late = unix_millis().saturating_sub(fresh_until);
+                if late == 0 {
+                    Lookup::Fresh(value)
+                } else if late <= self.stale_while_revalidate.as_millis() as u64 {
+                    Lookup::Stale {
+                        value,
+                        revalidate: true,
+                    }
+                } else if late <= self.stale_if_error.as_millis() as u64 {
+                    Lookup::Stale {
+                        value,
+                        revalidate: false,
+                    }
+                } else {
+                    Lookup::Miss
+                }
+            }
+        };
+        match &lookup {
+            Lookup::Fresh(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            Lookup::Stale {
+                revalidate: true, ..
+            } => {
+                self.stale.fetch_add(1, Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed)
+            }
+            _ => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        lookup
+    }
+
+    pub(crate) fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts results served stale because the call for them failed.
+    pub(crate) fn record_stale(&self, count: usize) {
+        self.stale.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Stores each value under its key, giving up when the cache timeout is
+    /// reached.
+    pub(crate) async fn set_all(&self, entries: &[(&str, &[u8])]) {
+        let fresh_until = unix_millis() + self.ttl.as_millis() as u64;
+        // The backend keeps the entry for as long as it may be served stale
+        let kept = self.ttl + self.stale_while_revalidate.max(self.stale_if_error);
+        let stored = entries
+            .iter()
+            .map(|(key, value)| {
+                let mut stored = format!("{fresh_until}\n").into_bytes();
+                stored.extend_from_slice(value);
+                (*key, stored)
+            })
+            .collect();
+        let stores = self.backend.set_many(stored, kept);
+        if !matches!(tokio::time::timeout(self.timeout, stores).await, Ok(Ok(()))) {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the caller should refresh `key`; false when another task
+    /// already is. Must be followed by [`CacheLayer::end_refresh`].
+    pub(crate) fn begin_refresh(&self, key: &str) -> bool {
+        self.lock_refreshing().insert(key.to_string())
+    }
+
+    pub(crate) fn end_refresh(&self, key: &str) {
+        self.lock_refreshing().remove(key);
+    }
+
+    fn lock_refreshing(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.refreshing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn clear(&self) {
+        self.backend.clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            stale: self.stale.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            ..self.backend.usage()
+        }
+    }
+}
+
+// Entries are stored as "<fresh until, in ms since the epoch>\n<result>" so
+// that replicas sharing a backend agree on when they go stale
+fn open_envelope(stored: &[u8]) -> Option<(u64, &[u8])> {
+    let newline = stored.iter().position(|&b| b == b'\n')?;
+    let fresh_until = std::str::from_utf8(&stored[..newline]).ok()?.parse().ok()?;
+    Some((fresh_until, &stored[newline + 1..]))
+}
+
+// Hex SHA-256 of `bytes`. The same on every replica, unlike `DefaultHasher`,
+// and collision resistant: whoever picks a user token or query parameter
+// must not be able to land on the key of another user's results.
+pub(crate) fn digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
@@ -1,208 +1,674 @@
-use crate::error::{Error, Result};
+use crate::batch::{BatchResponse, RecommendBatch};
+use crate::bulk::{BulkOptions, BulkResponse};
+use crate::cache::{CacheConfig, CacheLayer, CacheStats, Lookup, MemoryCache, RecommendCache};
+use crate::coalesce::{self, InFlight, Role};
+use crate::error::{check_result_count, Error, HostAttempt, Result, ValidationError};
+use crate::host::{HostPool, HostStatus, DEFAULT_HOST_DOWN_TTL};
 use crate::models::{
-    Model, RecommendRequest, RecommendResponse, TrendingFacetsRequest, TrendingFacetsResponse,
+    Model, RecommendRequest, RecommendResponse, RecommendResult, TrendingFacetsRequest,
+    TrendingFacetsResponse,
 };
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE};
-use reqwest::{Client, StatusCode};
-use serde::Serialize;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::options::RequestOptions;
+use crate::retry::{parse_retry_after, ExponentialBackoff, RetryPolicy};
+use crate::rules::{
+    DeletedAtResponse, RecommendRule, SearchRulesParams, SearchRulesResponse, UpdatedAtResponse,
+};
+use crate::secret::ApiKey;
+use crate::task::{RecommendTaskResponse, TaskStatus, WaitForTaskOptions};
+use futures_util::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::{Certificate, Client, Method, Proxy, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 const DEFAULT_SCHEME: &str = "https";
 const RECOMMEND_PATH: &str = "/1/indexes/*/recommendations";
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct RecommendClient {
     app_id: String,
-    api_key: String,
+    api_key: ApiKey,
     http: Client,
-    base_url: String,
-    hosts: Vec<String>,
-    host_cursor: Arc<AtomicUsize>,
+    hosts: Arc<HostPool>,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    retry_policy: Arc<dyn RetryPolicy>,
+    validate_requests: bool,
+    cache: Option<Arc<CacheLayer>>,
+    in_flight: Option<Arc<InFlight>>,
+}
+
+// Written by hand so that the key never ends up in logs
+impl fmt::Debug for RecommendClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecommendClient")
+            .field("app_id", &self.app_id)
+            .field("api_key", &self.api_key)
+            .field("hosts", &self.hosts)
+            .field("timeout", &self.timeout)
+            .field("retry_policy", &self.retry_policy)
+            .field("validate_requests", &self.validate_requests)
+            .field("cache", &self.cache)
+            .field("coalesce_requests", &self.in_flight.is_some())
+            .finish_non_exhaustive()
+    }
 }
 
 impl RecommendClient {
-    pub fn new(app_id: impl Into<String>, api_key: impl Into<String>) -> Self {
-        let app_id_str = app_id.into();
-        let hosts = get_default_hosts(&app_id_str);
-        Self::with_hosts(app_id_str, api_key, hosts)
+    /// # Panics
+    ///
+    /// When the app ID or API key is not a valid header value, e.g. because
+    /// it contains a newline ([`Error::InvalidHeader`]). Use
+    /// [`RecommendClient::try_new`] or [`RecommendClient::builder`] to get
+    /// the error instead.
+    pub fn new(app_id: impl Into<String>, api_key: impl Into<ApiKey>) -> Self {
+        Self::try_new(app_id, api_key).expect("failed to build RecommendClient")
     }
 
+    pub fn try_new(app_id: impl Into<String>, api_key: impl Into<ApiKey>) -> Result<Self> {
+        Self::builder(app_id, api_key).build()
+    }
+
+    /// # Panics
+    ///
+    /// Like [`RecommendClient::new`], or when the host does not make a valid
+    /// URL ([`Error::InvalidHost`]), see [`RecommendClient::try_with_custom_host`].
     pub fn with_custom_host(
         app_id: impl Into<String>,
-        api_key: impl Into<String>,
+        api_key: impl Into<ApiKey>,
         host: impl Into<String>,
     ) -> Self {
-        let app_id_str = app_id.into();
-        let host_str: String = host.into();
-        let base_url = format!("{DEFAULT_SCHEME}://{host_str}");
-        Self::with_hosts(app_id_str, api_key, vec![base_url])
+        Self::try_with_custom_host(app_id, api_key, host).expect("failed to build RecommendClient")
+    }
+
+    pub fn try_with_custom_host(
+        app_id: impl Into<String>,
+        api_key: impl Into<ApiKey>,
+        host: impl Into<String>,
+    ) -> Result<Self> {
+        Self::builder(app_id, api_key).custom_host(host).build()
     }
 
+    /// # Panics
+    ///
+    /// Like [`RecommendClient::new`], or when the host does not make a valid
+    /// URL ([`Error::InvalidHost`]), see [`RecommendClient::try_with_base_url`].
     pub fn with_base_url(
         app_id: impl Into<String>,
-        api_key: impl Into<String>,
+        api_key: impl Into<ApiKey>,
         base_url: impl Into<String>,
     ) -> Self {
-        let http = Client::builder()
-            .user_agent(USER_AGENT)
-            .build()
-            .expect("failed to build reqwest client");
-        let base = base_url.into();
-        Self {
-            app_id: app_id.into(),
-            api_key: api_key.into(),
-            http,
-            base_url: base.clone(),
-            hosts: vec![base],
-            host_cursor: Arc::new(AtomicUsize::new(0)),
-        }
+        Self::try_with_base_url(app_id, api_key, base_url).expect("failed to build RecommendClient")
+    }
+
+    pub fn try_with_base_url(
+        app_id: impl Into<String>,
+        api_key: impl Into<ApiKey>,
+        base_url: impl Into<String>,
+    ) -> Result<Self> {
+        Self::builder(app_id, api_key).base_url(base_url).build()
     }
 
+    /// # Panics
+    ///
+    /// Like [`RecommendClient::new`], or when `hosts` is empty or one of them
+    /// is not a valid URL ([`Error::InvalidHost`]), see
+    /// [`RecommendClient::try_with_hosts`].
     pub fn with_hosts(
         app_id: impl Into<String>,
-        api_key: impl Into<String>,
+        api_key: impl Into<ApiKey>,
         hosts: Vec<String>,
     ) -> Self {
-        let http = Client::builder()
-            .user_agent(USER_AGENT)
-            .build()
-            .expect("failed to build reqwest client");
-        let base_url = hosts
-            .first()
-            .cloned()
-            .unwrap_or_else(|| String::from("https://"));
-        Self {
-            app_id: app_id.into(),
-            api_key: api_key.into(),
-            http,
-            base_url,
-            hosts,
-            host_cursor: Arc::new(AtomicUsize::new(0)),
+        Self::try_with_hosts(app_id, api_key, hosts).expect("failed to build RecommendClient")
+    }
+
+    pub fn try_with_hosts(
+        app_id: impl Into<String>,
+        api_key: impl Into<ApiKey>,
+        hosts: Vec<String>,
+    ) -> Result<Self> {
+        Self::builder(app_id, api_key).hosts(hosts).build()
+    }
+
+    /// Start configuring a client. Like the `try_*` constructors, the builder
+    /// reports construction failures instead of panicking.
+    pub fn builder(
+        app_id: impl Into<String>,
+        api_key: impl Into<ApiKey>,
+    ) -> RecommendClientBuilder {
+        RecommendClientBuilder::new(app_id, api_key)
+    }
+
+    fn request_headers(&self, options: &RequestOptions) -> Result<HeaderMap> {
+        let mut headers = self.headers.clone();
+        for (name, value) in &options.headers {
+            let invalid = || Error::InvalidHeader { name: name.clone() };
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?,
+                HeaderValue::from_str(value).map_err(|_| invalid())?,
+            );
         }
+        Ok(headers)
     }
 
-    fn headers(&self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert(
-            "x-algolia-application-id",
-            HeaderValue::from_str(&self.app_id).unwrap(),
-        );
-        headers.insert(
-            "x-algolia-api-key",
-            HeaderValue::from_str(&self.api_key).unwrap(),
-        );
-        headers
+    async fn post_json<B: Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        body: &B,
+        options: &RequestOptions,
+    ) -> Result<R> {
+        self.call_json(Method::POST, RECOMMEND_PATH, Some(body), options)
+            .await
     }
 
-    async fn post_json<B: Serialize, R: serde::de::DeserializeOwned>(&self, body: &B) -> Result<R> {
-        // Start from a rotating cursor to distribute load across hosts
-        let total_hosts = self.hosts.len();
-        let start = if total_hosts == 0 {
-            0
+    async fn fetch_recommendations<T: serde::de::DeserializeOwned>(
+        &self,
+        requests: &[RecommendRequest],
+        options: &RequestOptions,
+    ) -> Result<RecommendResponse<T>> {
+        if self.cache.is_none() && self.in_flight.is_none() {
+            return self.post_recommendations(requests, options).await;
+        }
+        let results = self
+            .fetch_values(requests, options)
+            .await?
+            .into_iter()
+            .map(|(value, stale)| {
+                let mut result: RecommendResult<T> = serde_json::from_value(value)?;
+                result.stale = stale;
+                Ok(result)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecommendResponse { results })
+    }
+
+    // One result per request, with whether it is stale. Answers what it can
+    // from the cache and only sends the other requests.
+    pub(crate) async fn fetch_values(
+        &self,
+        requests: &[RecommendRequest],
+        options: &RequestOptions,
+    ) -> Result<Vec<(Value, bool)>> {
+        let Some(cache) = &self.cache else {
+            let sent: Vec<&RecommendRequest> = requests.iter().collect();
+            let results = self.send_requests(&sent, options).await?;
+            return Ok(results.into_iter().map(|value| (value, false)).collect());
+        };
+
+        let keys = requests
+            .iter()
+            .map(|request| Ok(cache.key(&options.request_key(request)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let lookups: Vec<Lookup> = if options.bypass_cache {
+            for _ in &keys {
+                cache.record_miss();
+            }
+            keys.iter().map(|_| Lookup::Miss).collect()
         } else {
-            self.host_cursor.fetch_add(1, Ordering::Relaxed) % total_hosts
+            cache.get_all(&keys).await
         };
 
-        let mut last_error: Option<Error> = None;
-        for attempt in 0..std::cmp::max(1, total_hosts) {
-            let idx = (start + attempt) % std::cmp::max(1, total_hosts);
-            let base = self
-                .hosts
-                .get(idx)
-                .cloned()
-                .unwrap_or_else(|| self.base_url.clone());
-            let url = format!("{base}{RECOMMEND_PATH}");
-
-            let req = self.http.post(&url).headers(self.headers()).json(body);
-
-            match req.send().await {
-                Ok(res) => {
-                    let status = res.status();
-                    let text = res.text().await?;
-                    if status.is_success() {
-                        let parsed = serde_json::from_str::<R>(&text)?;
-                        return Ok(parsed);
-                    }
+        // Each result with whether it is stale
+        let mut cached: Vec<Option<(Value, bool)>> = vec![None; requests.len()];
+        let mut fallbacks: Vec<Option<Value>> = vec![None; requests.len()];
+        let mut revalidate = Vec::new();
+        for (i, lookup) in lookups.into_iter().enumerate() {
+            match lookup {
+                Lookup::Fresh(value) => cached[i] = Some((value, false)),
+                Lookup::Stale {
+                    value,
+                    revalidate: true,
+                } => {
+                    cached[i] = Some((value, true));
+                    revalidate.push(i);
+                }
+                Lookup::Stale { value, .. } => fallbacks[i] = Some(value),
+                Lookup::Miss => {}
+            }
+        }
+        if !revalidate.is_empty() {
+            self.spawn_refresh(
+                cache,
+                revalidate
+                    .iter()
+                    .map(|&i| (keys[i].clone(), requests[i].clone()))
+                    .collect(),
+                options,
+            );
+        }
 
-                    // Retry on 5xx and 429 by moving to next host
-                    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
-                        last_error = Some(Error::Api {
-                            status: status.as_u16(),
-                            message: serde_json::from_str::<serde_json::Value>(&text)
-                                .ok()
-                                .and_then(|v| {
-                                    v.get("message")
-                                        .and_then(|m| m.as_str())
-                                        .map(|s| s.to_string())
-                                }),
-                            body: text,
-                        });
-                        continue;
-                    } else {
-                        return Err(Error::Api {
-                            status: status.as_u16(),
-                            message: serde_json::from_str::<serde_json::Value>(&text)
-                                .ok()
-                                .and_then(|v| {
-                                    v.get("message")
-                                        .and_then(|m| m.as_str())
-                                        .map(|s| s.to_string())
-                                }),
-                            body: text,
-                        });
+        let misses: Vec<usize> = (0..requests.len())
+            .filter(|&i| cached[i].is_none())
+            .collect();
+        if !misses.is_empty() {
+            let sent: Vec<&RecommendRequest> = misses.iter().map(|&i| &requests[i]).collect();
+            match self.send_requests(&sent, options).await {
+                Ok(results) => {
+                    let values: Vec<String> = results.iter().map(Value::to_string).collect();
+                    let entries: Vec<(&str, &[u8])> = misses
+                        .iter()
+                        .zip(&values)
+                        .map(|(&i, value)| (keys[i].as_str(), value.as_bytes()))
+                        .collect();
+                    cache.set_all(&entries).await;
+                    for (&i, value) in misses.iter().zip(results) {
+                        cached[i] = Some((value, false));
                     }
                 }
-                Err(e) => {
-                    // Retry on network/connect/timeout errors
-                    if e.is_connect() || e.is_timeout() || e.is_request() {
-                        last_error = Some(Error::Http(e));
-                        continue;
-                    } else {
-                        return Err(Error::Http(e));
+                // Better an outdated carousel than an empty one
+                Err(err)
+                    if err.is_retryable() && misses.iter().all(|&i| fallbacks[i].is_some()) =>
+                {
+                    cache.record_stale(misses.len());
+                    for &i in &misses {
+                        cached[i] = fallbacks[i].take().map(|value| (value, true));
                     }
                 }
+                Err(Error::MissingResult { index }) => {
+                    return Err(Error::MissingResult {
+                        index: misses[index],
+                    })
+                }
+                Err(err) => return Err(err),
             }
         }
 
-        Err(last_error.unwrap_or_else(|| Error::Api {
-            status: 0,
-            message: Some("all hosts failed".to_string()),
-            body: String::new(),
-        }))
+        Ok(cached.into_iter().flatten().collect())
     }
 
-    // Public API
-    pub async fn get_recommendations<T: serde::de::DeserializeOwned + Send + 'static>(
+    // Refreshes stale entries after they were served, skipping the ones that
+    // another call is already refreshing
+    fn spawn_refresh(
         &self,
-        requests: Vec<RecommendRequest>,
+        cache: &Arc<CacheLayer>,
+        stale: Vec<(String, RecommendRequest)>,
+        options: &RequestOptions,
+    ) {
+        let stale: Vec<_> = stale
+            .into_iter()
+            .filter(|(key, _)| cache.begin_refresh(key))
+            .collect();
+        if stale.is_empty() {
+            return;
+        }
+        let (client, cache, options) = (self.clone(), cache.clone(), options.clone());
+        tokio::spawn(async move {
+            let requests: Vec<&RecommendRequest> = stale.iter().map(|(_, r)| r).collect();
+            // On failure the entries stay stale and the next call tries again
+            if let Ok(results) = client.send_requests(&requests, &options).await {
+                let values: Vec<String> = results.iter().map(Value::to_string).collect();
+                let entries: Vec<(&str, &[u8])> = stale
+                    .iter()
+                    .zip(&values)
+                    .map(|((key, _), value)| (key.as_str(), value.as_bytes()))
+                    .collect();
+                cache.set_all(&entries).await;
+            }
+            for (key, _) in &stale {
+                cache.end_refresh(key);
+            }
+        });
+    }
+
+    pub(crate) fn check_requests(&self, requests: &[RecommendRequest]) -> Result<()> {
+        if self.validate_requests {
+            check_all(requests, RecommendRequest::validate)?;
+        }
+        Ok(())
+    }
+
+    // Sends `requests` and returns one result per request, in order. With
+    // coalescing on, a request identical to one already in flight waits for
+    // that call's result instead of being sent again.
+    async fn send_requests(
+        &self,
+        requests: &[&RecommendRequest],
+        options: &RequestOptions,
+    ) -> Result<Vec<Value>> {
+        let Some(in_flight) = &self.in_flight else {
+            return self.post_raw_results(requests, options).await;
+        };
+
+        // A caller only waits for a call made with its own timeout and retry
+        // policy, not one that may take much longer than it would have
+        let limits = format!(
+            "|{:?}|{:?}",
+            options.timeout.or(self.timeout),
+            self.retry_policy
+        );
+        let mut led = Vec::new();
+        let mut joined = Vec::new();
+        for (i, request) in requests.iter().enumerate() {
+            match in_flight.join(options.request_key(request)? + &limits) {
+                Role::Leader(flight) => led.push((i, flight)),
+                Role::Follower(rx) => joined.push((i, rx)),
+            }
+        }
+
+        // Results are published as soon as they arrive: a request may follow
+        // another one of the same call
+        let lead = async {
+            if led.is_empty() {
+                return Ok(Vec::new());
+            }
+            let sent: Vec<&RecommendRequest> = led.iter().map(|&(i, _)| requests[i]).collect();
+            match self.post_raw_results(&sent, options).await {
+                Ok(results) => Ok(led
+                    .into_iter()
+                    .zip(results)
+                    .map(|((i, flight), result)| {
+                        flight.finish(result.clone());
+                        (i, result)
+                    })
+                    .collect()),
+                // Dropping the flights lets their followers send them
+                Err(Error::MissingResult { index }) => Err(Error::MissingResult {
+                    index: led[index].0,
+                }),
+                Err(err @ Error::ResultCountMismatch { .. }) => Err(err),
+                Err(err) => Err(in_flight.fail(led.into_iter().map(|(_, f)| f).collect(), err)),
+            }
+        };
+        let follow = futures_util::future::join_all(
+            joined
+                .into_iter()
+                .map(|(i, rx)| async move { (i, coalesce::follow(rx).await) }),
+        );
+        let (led, followed) = futures_util::join!(lead, follow);
+
+        let mut results: Vec<Option<Value>> = vec![None; requests.len()];
+        for (i, result) in led? {
+            results[i] = Some(result);
+        }
+        let mut abandoned = Vec::new();
+        for (i, outcome) in followed {
+            match outcome {
+                Some(result) => results[i] = Some(result?),
+                None => abandoned.push(i),
+            }
+        }
+        if !abandoned.is_empty() {
+            let sent: Vec<&RecommendRequest> = abandoned.iter().map(|&i| requests[i]).collect();
+            let retried = match self.post_raw_results(&sent, options).await {
+                Err(Error::MissingResult { index }) => {
+                    return Err(Error::MissingResult {
+                        index: abandoned[index],
+                    })
+                }
+                retried => retried?,
+            };
+            for (i, result) in abandoned.into_iter().zip(retried) {
+                results[i] = Some(result);
+            }
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    async fn post_raw_results(
+        &self,
+        requests: &[&RecommendRequest],
+        options: &RequestOptions,
+    ) -> Result<Vec<Value>> {
+        #[derive(Serialize)]
+        struct Body<'a> {
+            requests: &'a [&'a RecommendRequest],
+        }
+        #[derive(Deserialize)]
+        struct Raw {
+            results: Vec<Value>,
+        }
+        let raw: Raw = self.post_json(&Body { requests }, options).await?;
+        check_result_count(requests.len(), raw.results.len())?;
+        Ok(raw.results)
+    }
+
+    async fn post_recommendations<T: serde::de::DeserializeOwned>(
+        &self,
+        requests: &[RecommendRequest],
+        options: &RequestOptions,
     ) -> Result<RecommendResponse<T>> {
         #[derive(Serialize)]
         struct Body<'a> {
             requests: &'a [RecommendRequest],
         }
-        let body = Body {
-            requests: &requests,
-        };
-        self.post_json::<_, RecommendResponse<T>>(&body).await
+        self.post_json(&Body { requests }, options).await
+    }
+
+    // Sends `body` (if any) to `path` on each host in turn until one answers
+    async fn call_json<B: Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        options: &RequestOptions,
+    ) -> Result<R> {
+        let headers = self.request_headers(options)?;
+        let timeout = options.timeout.or(self.timeout);
+
+        // Never empty, `build` rejects an empty host list
+        let plan = self.hosts.plan();
+        let max_attempts = std::cmp::max(1, self.retry_policy.max_attempts());
+        let mut attempts = Vec::new();
+        for attempt in 0..max_attempts {
+            let (base, retry_count) = &plan[attempt as usize % plan.len()];
+            let url = format!("{base}{path}");
+
+            let mut req = self
+                .http
+                .request(method.clone(), &url)
+                .headers(headers.clone())
+                .query(&options.query_params);
+            if let Some(body) = body {
+                req = req.json(body);
+            }
+            if let Some(timeout) = timeout {
+                // Hosts that keep timing out get proportionally more time
+                req = req.timeout(timeout * (retry_count + 1));
+            }
+
+            let error = match send(req).await {
+                Ok(text) => {
+                    self.hosts.mark_up(base);
+                    return Ok(serde_json::from_str::<R>(&text)?);
+                }
+                Err(error) => error,
+            };
+
+            // Retry on 5xx, 429 and network errors on the next host, after a backoff
+            match &error {
+                // Rate limits apply to the whole application, the host is fine
+                Error::RateLimited { .. } => {}
+                Error::Timeout(_) => self.hosts.mark_timed_out(base),
+                e if e.is_retryable() => self.hosts.mark_down(base),
+                _ => {
+                    self.hosts.mark_up(base);
+                    return Err(error);
+                }
+            }
+            let retry_after = match &error {
+                Error::RateLimited { retry_after, .. } => *retry_after,
+                _ => None,
+            };
+            attempts.push(HostAttempt {
+                host: base.clone(),
+                error,
+            });
+
+            if attempt + 1 < max_attempts {
+                let delay = self.retry_policy.backoff(attempt + 1, retry_after);
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+
+        Err(Error::AllHostsFailed { attempts })
+    }
+
+    // Public API
+    /// A client sharing this one's connection pool and host state, but using
+    /// another retry policy. Meant for overriding the policy of a single call:
+    /// `client.with_retry_policy(policy).get_recommendations(...)`.
+    pub fn with_retry_policy(&self, policy: impl RetryPolicy + 'static) -> Self {
+        Self {
+            retry_policy: Arc::new(policy),
+            ..self.clone()
+        }
+    }
+
+    /// Counters of the recommendations cache, `None` when it is disabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Drop every cached recommendation. Counters are kept.
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Current state of every configured host, as seen by the retry logic.
+    pub fn host_statuses(&self) -> Vec<HostStatus> {
+        self.hosts.statuses()
+    }
+
+    pub async fn get_recommendations<T: serde::de::DeserializeOwned + Send + 'static>(
+        &self,
+        requests: Vec<RecommendRequest>,
+    ) -> Result<RecommendResponse<T>> {
+        self.get_recommendations_with_options(requests, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn get_recommendations_with_options<
+        T: serde::de::DeserializeOwned + Send + 'static,
+    >(
+        &self,
+        requests: Vec<RecommendRequest>,
+        options: &RequestOptions,
+    ) -> Result<RecommendResponse<T>> {
+        self.check_requests(&requests)?;
+        self.fetch_recommendations(&requests, options).await
+    }
+
+    /// Sends `requests` in chunks of `bulk.chunk_size`, with at most
+    /// `bulk.concurrency` calls in flight. A failed chunk doesn't stop the
+    /// others, see [`BulkResponse`].
+    pub async fn get_recommendations_bulk<T: serde::de::DeserializeOwned + Send + 'static>(
+        &self,
+        requests: Vec<RecommendRequest>,
+        bulk: &BulkOptions,
+    ) -> Result<BulkResponse<T>> {
+        self.get_recommendations_bulk_with_options(requests, bulk, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn get_recommendations_bulk_with_options<
+        T: serde::de::DeserializeOwned + Send + 'static,
+    >(
+        &self,
+        requests: Vec<RecommendRequest>,
+        bulk: &BulkOptions,
+        options: &RequestOptions,
+    ) -> Result<BulkResponse<T>> {
+        // Validated up front so that positions refer to the whole input
+        if self.validate_requests {
+            check_all(&requests, RecommendRequest::validate)?;
+        }
+        let chunk_size = std::cmp::max(1, bulk.chunk_size);
+        let outcomes = futures_util::stream::iter(
+            requests
+                .chunks(chunk_size)
+                .map(|chunk| self.fetch_recommendations::<T>(chunk, options)),
+        )
+        .buffered(std::cmp::max(1, bulk.concurrency))
+        .collect::<Vec<_>>()
+        .await;
+        Ok(BulkResponse::from_chunks(
+            requests.len(),
+            chunk_size,
+            outcomes,
+        ))
+    }
+
+    /// Sends every request of `batch` in one call, see [`RecommendBatch`].
+    /// Like `get_recommendations`, goes through the cache and coalescing when
+    /// enabled. A result missing from the answer fails the whole call.
+    pub async fn get_recommendations_batch(&self, batch: RecommendBatch) -> Result<BatchResponse> {
+        self.get_recommendations_batch_with_options(batch, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn get_recommendations_batch_with_options(
+        &self,
+        batch: RecommendBatch,
+        options: &RequestOptions,
+    ) -> Result<BatchResponse> {
+        if self.validate_requests {
+            check_all(&batch.requests, RecommendRequest::validate)?;
+        }
+        if self.cache.is_none() && self.in_flight.is_none() {
+            #[derive(Serialize)]
+            struct Body<'a> {
+                requests: &'a [RecommendRequest],
+            }
+            let body = Body {
+                requests: &batch.requests,
+            };
+            let response: BatchResponse = self.post_json(&body, options).await?;
+            check_result_count(batch.requests.len(), response.results.len())?;
+            return Ok(BatchResponse {
+                batch: batch.id,
+                ..response
+            });
+        }
+        let (results, stale) = self
+            .fetch_values(&batch.requests, options)
+            .await?
+            .into_iter()
+            .unzip();
+        Ok(BatchResponse {
+            batch: batch.id,
+            results,
+            stale,
+        })
     }
 
     pub async fn get_trending_facets(
         &self,
         requests: Vec<TrendingFacetsRequest>,
     ) -> Result<TrendingFacetsResponse> {
-        if requests
-            .iter()
-            .any(|r| !matches!(r.model, Model::TrendingFacets))
-        {
-            return Err(Error::Api {
-                status: StatusCode::BAD_REQUEST.as_u16(),
-                message: Some("all requests must use model=trending-facets".to_string()),
-                body: String::new(),
-            });
+        self.get_trending_facets_with_options(requests, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn get_trending_facets_with_options(
+        &self,
+        requests: Vec<TrendingFacetsRequest>,
+        options: &RequestOptions,
+    ) -> Result<TrendingFacetsResponse> {
+        if self.validate_requests {
+            check_all(&requests, TrendingFacetsRequest::validate)?;
+        } else {
+            // The response shape is specific to trending facets, mixing models
+            // would fail to parse anyway
+            check_all(&requests, |r| {
+                if r.model == Model::TrendingFacets {
+                    Ok(())
+                } else {
+                    Err(vec![ValidationError::new(
+                        "model",
+                        format!(
+                            "all requests must use model=trending-facets, got {}",
+                            r.model
+                        ),
+                    )])
+                }
+            })?;
         }
         #[derive(Serialize)]
         struct Body<'a> {
@@ -211,8 +677,509 @@ impl RecommendClient {
         let body = Body {
             requests: &requests,
         };
-        self.post_json::<_, TrendingFacetsResponse>(&body).await
+        self.post_json::<_, TrendingFacetsResponse>(&body, options)
+            .await
+    }
+    /// Search the Recommend rules of `index_name` for `model`.
+    pub async fn search_recommend_rules(
+        &self,
+        index_name: &str,
+        model: &Model,
+        params: &SearchRulesParams,
+    ) -> Result<SearchRulesResponse> {
+        let path = format!("{}/search", rules_path(index_name, model));
+        self.call_json(
+            Method::POST,
+            &path,
+            Some(params),
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    pub async fn get_recommend_rule(
+        &self,
+        index_name: &str,
+        model: &Model,
+        object_id: &str,
+    ) -> Result<RecommendRule> {
+        let path = format!(
+            "{}/{}",
+            rules_path(index_name, model),
+            encode_segment(object_id)
+        );
+        self.call_json(Method::GET, &path, None::<&()>, &RequestOptions::default())
+            .await
+    }
+
+    /// Create or replace `rules`, matched on their `objectID`.
+    pub async fn save_recommend_rules(
+        &self,
+        index_name: &str,
+        model: &Model,
+        rules: &[RecommendRule],
+    ) -> Result<UpdatedAtResponse> {
+        if self.validate_requests {
+            check_all(rules, RecommendRule::validate)?;
+        }
+        let path = format!("{}/batch", rules_path(index_name, model));
+        self.call_json(
+            Method::POST,
+            &path,
+            Some(&rules),
+            &RequestOptions::default(),
+        )
+        .await
     }
+
+    pub async fn delete_recommend_rule(
+        &self,
+        index_name: &str,
+        model: &Model,
+        object_id: &str,
+    ) -> Result<DeletedAtResponse> {
+        let path = format!(
+            "{}/{}",
+            rules_path(index_name, model),
+            encode_segment(object_id)
+        );
+        self.call_json(
+            Method::DELETE,
+            &path,
+            None::<&()>,
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// Whether the write identified by `task_id` is live yet.
+    pub async fn get_recommend_status(
+        &self,
+        index_name: &str,
+        model: &Model,
+        task_id: i64,
+    ) -> Result<RecommendTaskResponse> {
+        let path = format!(
+            "/1/indexes/{}/{}/task/{task_id}",
+            encode_segment(index_name),
+            encode_segment(model.as_str())
+        );
+        self.call_json(Method::GET, &path, None::<&()>, &RequestOptions::default())
+            .await
+    }
+
+    /// Polls [`RecommendClient::get_recommend_status`] until the task is
+    /// published, or fails with [`Error::TaskTimeout`] after `options.max_wait`.
+    pub async fn wait_for_task(
+        &self,
+        index_name: &str,
+        model: &Model,
+        task_id: i64,
+        options: &WaitForTaskOptions,
+    ) -> Result<TaskStatus> {
+        let started = tokio::time::Instant::now();
+        let mut interval = options.first_interval();
+        loop {
+            let task = self
+                .get_recommend_status(index_name, model, task_id)
+                .await?;
+            if task.status == TaskStatus::Published {
+                return Ok(task.status);
+            }
+
+            let waited = started.elapsed();
+            if waited >= options.max_wait {
+                return Err(Error::TaskTimeout { task_id, waited });
+            }
+            // Never sleep past the deadline, the last poll happens right at it
+            tokio::time::sleep(interval.min(options.max_wait - waited)).await;
+            interval = options.next_interval(interval);
+        }
+    }
+}
+
+/// Configures and builds a [`RecommendClient`].
+///
+/// Timeouts, proxies, pool and TLS settings are applied to the internally
+/// built `reqwest::Client`. They are ignored when an external client is
+/// supplied through [`RecommendClientBuilder::http_client`], which is the way
+/// to share one connection pool between several clients.
+#[derive(Debug)]
+pub struct RecommendClientBuilder {
+    app_id: String,
+    api_key: ApiKey,
+    hosts: Option<Vec<String>>,
+    http: Option<Client>,
+    user_agent: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    proxies: Vec<Proxy>,
+    no_proxy: bool,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    root_certificates: Vec<Certificate>,
+    tls_built_in_root_certs: Option<bool>,
+    host_down_ttl: Duration,
+    retry_policy: Arc<dyn RetryPolicy>,
+    validate_requests: bool,
+    cache: Option<(Arc<dyn RecommendCache>, Duration)>,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+    cache_timeout: Duration,
+    coalesce_requests: bool,
+}
+
+impl RecommendClientBuilder {
+    pub fn new(app_id: impl Into<String>, api_key: impl Into<ApiKey>) -> Self {
+        Self {
+            app_id: app_id.into(),
+            api_key: api_key.into(),
+            hosts: None,
+            http: None,
+            user_agent: USER_AGENT.to_string(),
+            timeout: None,
+            connect_timeout: None,
+            read_timeout: None,
+            proxies: Vec::new(),
+            no_proxy: false,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            root_certificates: Vec::new(),
+            tls_built_in_root_certs: None,
+            host_down_ttl: DEFAULT_HOST_DOWN_TTL,
+            retry_policy: Arc::new(ExponentialBackoff::default()),
+            validate_requests: true,
+            cache: None,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+            cache_timeout: Duration::from_secs(1),
+            coalesce_requests: false,
+        }
+    }
+
+    /// Replace the default Algolia hosts with full base URLs (scheme included),
+    /// possibly with a path prefix.
+    pub fn hosts(mut self, hosts: Vec<String>) -> Self {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    /// Use a single host name, reached over https.
+    pub fn custom_host(self, host: impl Into<String>) -> Self {
+        let host: String = host.into();
+        self.hosts(vec![format!("{DEFAULT_SCHEME}://{host}")])
+    }
+
+    /// Use a single base URL, e.g. a local mock server.
+    pub fn base_url(self, base_url: impl Into<String>) -> Self {
+        self.hosts(vec![base_url.into()])
+    }
+
+    /// Use an externally built `reqwest::Client` instead of building one.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http = Some(client);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Total timeout for a single HTTP attempt, from connect to end of body.
+    /// It is scaled up for hosts that recently timed out and also applies
+    /// when an external `reqwest::Client` is used.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Ignore proxies configured through the environment.
+    pub fn no_proxy(mut self) -> Self {
+        self.no_proxy = true;
+        self
+    }
+
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn add_root_certificate(mut self, cert: Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    pub fn tls_built_in_root_certs(mut self, enabled: bool) -> Self {
+        self.tls_built_in_root_certs = Some(enabled);
+        self
+    }
+
+    /// How long a host that failed or timed out is skipped before being
+    /// retried in priority again.
+    pub fn host_down_ttl(mut self, ttl: Duration) -> Self {
+        self.host_down_ttl = ttl;
+        self
+    }
+
+    /// Retry policy used by every call, see [`ExponentialBackoff`] for the
+    /// default.
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Run `validate()` on every request before sending it (on by default).
+    /// Turn it off to let the API be the judge, e.g. right after Algolia
+    /// relaxes a limit.
+    pub fn validate_requests(mut self, enabled: bool) -> Self {
+        self.validate_requests = enabled;
+        self
+    }
+
+    /// Cache `get_recommendations`, `get_recommendations_batch` and bulk
+    /// results in memory, per request. Requests of a batch found in the
+    /// cache are not sent again. Trending facets are not cached.
+    pub fn cache(mut self, config: CacheConfig) -> Self {
+        let ttl = config.ttl;
+        self.cache = Some((Arc::new(MemoryCache::new(config)), ttl));
+        self
+    }
+
+    /// Like [`RecommendClientBuilder::cache`], with another storage, e.g. one
+    /// shared by several replicas.
+    pub fn cache_backend(mut self, backend: impl RecommendCache + 'static, ttl: Duration) -> Self {
+        self.cache = Some((Arc::new(backend), ttl));
+        self
+    }
+
+    /// Limit for looking up the requests of one call in the cache, and for
+    /// storing their results: 1s by default. Past it the cache is skipped,
+    /// so a slow backend delays a call by at most twice this.
+    pub fn cache_timeout(mut self, timeout: Duration) -> Self {
+        self.cache_timeout = timeout;
+        self
+    }
+
+    /// Let concurrent `get_recommendations` and `get_recommendations_batch`
+    /// calls share a single upstream call for identical requests, sent with
+    /// the same headers, query parameters, timeout and retry policy. Each caller gets its own copy
+    /// of the result, and the failure of a shared call as [`Error::Coalesced`].
+    pub fn coalesce_requests(mut self, enabled: bool) -> Self {
+        self.coalesce_requests = enabled;
+        self
+    }
+
+    /// Keep serving cached results for `window` past their TTL, refreshing
+    /// them in the background. Such results have
+    /// [`RecommendResult::stale`](crate::RecommendResult::stale) set.
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = window;
+        self
+    }
+
+    /// When a call fails with a retryable error, e.g. every host is down,
+    /// answer with results that expired less than `window` ago instead.
+    /// Such results have [`RecommendResult::stale`](crate::RecommendResult::stale)
+    /// set. Only done when every request of the call has one.
+    pub fn stale_if_error(mut self, window: Duration) -> Self {
+        self.stale_if_error = window;
+        self
+    }
+
+    pub fn build(self) -> Result<RecommendClient> {
+        let http = match self.http {
+            Some(http) => http,
+            None => {
+                let mut builder = Client::builder().user_agent(self.user_agent);
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.read_timeout {
+                    builder = builder.read_timeout(timeout);
+                }
+                if self.no_proxy {
+                    builder = builder.no_proxy();
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                if let Some(max) = self.pool_max_idle_per_host {
+                    builder = builder.pool_max_idle_per_host(max);
+                }
+                if let Some(timeout) = self.pool_idle_timeout {
+                    builder = builder.pool_idle_timeout(timeout);
+                }
+                for cert in self.root_certificates {
+                    builder = builder.add_root_certificate(cert);
+                }
+                if let Some(enabled) = self.tls_built_in_root_certs {
+                    builder = builder.tls_built_in_root_certs(enabled);
+                }
+                builder.build()?
+            }
+        };
+
+        let headers = default_headers(&self.app_id, &self.api_key)?;
+        let hosts = self
+            .hosts
+            .unwrap_or_else(|| get_default_hosts(&self.app_id))
+            .into_iter()
+            .map(base_url)
+            .collect::<Result<Vec<_>>>()?;
+        if hosts.is_empty() {
+            return Err(Error::InvalidHost(String::from("no host given")));
+        }
+
+        let cache = self.cache.map(|(backend, ttl)| {
+            Arc::new(CacheLayer::new(
+                backend,
+                ttl,
+                self.stale_while_revalidate,
+                self.stale_if_error,
+                self.cache_timeout,
+                &self.app_id,
+                self.api_key.expose_secret(),
+            ))
+        });
+
+        Ok(RecommendClient {
+            app_id: self.app_id,
+            api_key: self.api_key,
+            http,
+            hosts: Arc::new(HostPool::new(hosts, self.host_down_ttl)),
+            headers,
+            timeout: self.timeout,
+            retry_policy: self.retry_policy,
+            validate_requests: self.validate_requests,
+            cache,
+            in_flight: self
+                .coalesce_requests
+                .then(|| Arc::new(InFlight::default())),
+        })
+    }
+}
+
+// Paths are appended to the host as is: it must be a scheme and a host name,
+// optionally followed by a port and a path prefix, e.g. of a reverse proxy.
+// A trailing `/` is dropped.
+fn base_url(host: String) -> Result<String> {
+    match reqwest::Url::parse(&host) {
+        Ok(url) if url.has_host() && url.query().is_none() && url.fragment().is_none() => {
+            Ok(host.trim_end_matches('/').to_string())
+        }
+        _ => Err(Error::InvalidHost(format!("{host:?} is not a base URL"))),
+    }
+}
+
+// Runs `validate` on each request, tagging problems with the request position
+fn check_all<R>(
+    requests: &[R],
+    validate: impl Fn(&R) -> std::result::Result<(), Vec<ValidationError>>,
+) -> Result<()> {
+    let errors: Vec<ValidationError> = requests
+        .iter()
+        .enumerate()
+        .filter_map(|(index, r)| validate(r).err().map(|errors| (index, errors)))
+        .flat_map(|(index, errors)| {
+            errors.into_iter().map(move |e| ValidationError {
+                index: Some(index),
+                ..e
+            })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation { errors })
+    }
+}
+
+// One HTTP attempt, returning the body of a 2xx response
+async fn send(req: RequestBuilder) -> Result<String> {
+    let classify = |e: reqwest::Error| {
+        if e.is_timeout() {
+            Error::Timeout(e)
+        } else {
+            Error::Http(e)
+        }
+    };
+
+    let res = req.send().await.map_err(classify)?;
+    let status = res.status();
+    let retry_after = parse_retry_after(res.headers());
+    let text = res.text().await.map_err(classify)?;
+    if status.is_success() {
+        Ok(text)
+    } else {
+        Err(Error::from_response(status, retry_after, text))
+    }
+}
+
+fn rules_path(index_name: &str, model: &Model) -> String {
+    format!(
+        "/1/indexes/{}/{}/recommend/rules",
+        encode_segment(index_name),
+        encode_segment(model.as_str())
+    )
+}
+
+// Percent-encodes everything but unreserved characters, like JavaScript's
+// encodeURIComponent, so that names with `/`, `?` or spaces stay one segment
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+// Built once per client so that invalid credentials fail at construction
+fn default_headers(app_id: &str, api_key: &ApiKey) -> Result<HeaderMap> {
+    let invalid = |name: &'static str| {
+        move |_| Error::InvalidHeader {
+            name: name.to_string(),
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        "x-algolia-application-id",
+        HeaderValue::from_str(app_id).map_err(invalid("x-algolia-application-id"))?,
+    );
+    let mut key =
+        HeaderValue::from_str(api_key.expose_secret()).map_err(invalid("x-algolia-api-key"))?;
+    key.set_sensitive(true);
+    headers.insert("x-algolia-api-key", key);
+    Ok(headers)
 }
 
 fn get_default_hosts(app_id: &str) -> Vec<String> {
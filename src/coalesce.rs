@@ -0,0 +1,99 @@
+//! Single-flight deduplication of identical recommendation requests.
+
+use crate::error::Error;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+type Outcome = std::result::Result<Value, Arc<Error>>;
+
+/// The requests being sent right now, by key, with the channel their result
+/// will be published on.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    calls: Mutex<HashMap<String, watch::Receiver<Option<Outcome>>>>,
+}
+
+pub(crate) enum Role {
+    /// First to ask, sends the request and publishes its result.
+    Leader(Flight),
+    /// Waits for the leader's result.
+    Follower(watch::Receiver<Option<Outcome>>),
+}
+
+impl InFlight {
+    pub(crate) fn join(self: &Arc<Self>, key: String) -> Role {
+        let mut calls = self.lock();
+        if let Some(rx) = calls.get(&key) {
+            return Role::Follower(rx.clone());
+        }
+        let (tx, rx) = watch::channel(None);
+        calls.insert(key.clone(), rx);
+        Role::Leader(Flight {
+            key,
+            registered: true,
+            tx,
+            in_flight: self.clone(),
+        })
+    }
+
+    /// Publishes `err` to the followers of `flights`. The leader gets `err`
+    /// back as is when nobody joined, and shares it otherwise.
+    pub(crate) fn fail(&self, mut flights: Vec<Flight>, err: Error) -> Error {
+        let followers: usize = {
+            // Nobody can join once the keys are gone
+            let mut calls = self.lock();
+            for flight in &mut flights {
+                calls.remove(&flight.key);
+                flight.registered = false;
+            }
+            flights.iter().map(|f| f.tx.receiver_count()).sum()
+        };
+        if followers == 0 {
+            return err;
+        }
+        let shared = Arc::new(err);
+        for flight in flights {
+            flight.tx.send_replace(Some(Err(shared.clone())));
+        }
+        Error::Coalesced(shared)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, watch::Receiver<Option<Outcome>>>> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A request led by the current caller. Dropping it unfinished, e.g. when the
+/// leader's future is cancelled, lets the followers send the request
+/// themselves.
+pub(crate) struct Flight {
+    key: String,
+    // Whether `key` in `in_flight` is still this flight's
+    registered: bool,
+    tx: watch::Sender<Option<Outcome>>,
+    in_flight: Arc<InFlight>,
+}
+
+impl Flight {
+    pub(crate) fn finish(self, value: Value) {
+        self.tx.send_replace(Some(Ok(value)));
+    }
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        if self.registered {
+            self.in_flight.lock().remove(&self.key);
+        }
+    }
+}
+
+/// The leader's outcome, or `None` when it gave up without one.
+pub(crate) async fn follow(
+    mut rx: watch::Receiver<Option<Outcome>>,
+) -> Option<crate::error::Result<Value>> {
+    let outcome = rx.wait_for(Option::is_some).await.ok()?.clone()?;
+    Some(outcome.map_err(Error::Coalesced))
+}
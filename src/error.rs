@@ -1,3 +1,9 @@
+use crate::bulk::ChunkFailure;
+use reqwest::StatusCode;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,6 +14,83 @@ pub enum Error {
     #[error("serde error: {0}")]
     Serde(#[from] serde_json::Error),
 
+    // The offending value is left out on purpose, it may be a secret
+    #[error("invalid header {name}")]
+    InvalidHeader { name: String },
+
+    #[error("invalid host: {0}")]
+    InvalidHost(String),
+
+    #[error("request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+
+    #[error("invalid request: {}", join(errors))]
+    Validation { errors: Vec<ValidationError> },
+
+    #[error("Algolia API error (status {status}): unauthorized: {message:?}")]
+    Unauthorized {
+        status: u16,
+        message: Option<String>,
+        body: String,
+    },
+
+    #[error("Algolia API error (status 404): not found: {message:?}")]
+    NotFound {
+        message: Option<String>,
+        body: String,
+    },
+
+    #[error(
+        "Algolia API error (status 429): rate limited (retry after {retry_after:?}): {message:?}"
+    )]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: Option<String>,
+        body: String,
+    },
+
+    #[error("all hosts failed after {} attempt(s){}", attempts.len(), last_cause(attempts))]
+    AllHostsFailed { attempts: Vec<HostAttempt> },
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid rule file {}: {message}", path.display())]
+    RuleFile { path: PathBuf, message: String },
+
+    #[error("cache error: {0}")]
+    Cache(String),
+
+    #[error("no result for request {index} of the batch")]
+    MissingResult { index: usize },
+
+    /// The API answered a call of `expected` requests with more results,
+    /// which can't be matched to the requests.
+    #[error("expected {expected} result(s), got {got}")]
+    ResultCountMismatch { expected: usize, got: usize },
+
+    /// A [`BatchHandle`](crate::BatchHandle) read from the response to
+    /// another batch than the one that made it.
+    #[error("the handle of request {index} belongs to another batch")]
+    ForeignBatchHandle { index: usize },
+
+    #[error("{} chunk(s) of the bulk call failed{}", failures.len(), first_chunk_failure(failures))]
+    BulkFailed { failures: Vec<ChunkFailure> },
+
+    #[error("task {task_id} was not published after {waited:?}")]
+    TaskTimeout { task_id: i64, waited: Duration },
+
+    /// The failure of a call shared by several callers, see
+    /// [`RecommendClientBuilder::coalesce_requests`](crate::RecommendClientBuilder::coalesce_requests)
+    /// and [`RecommendLoader`](crate::RecommendLoader).
+    #[error("shared call failed: {0}")]
+    Coalesced(#[source] Arc<Error>),
+
+    /// The background task of a [`RecommendLoader`](crate::RecommendLoader)
+    /// is gone, e.g. because its runtime shut down.
+    #[error("the recommend loader is no longer running")]
+    LoaderStopped,
+
     #[error("Algolia API error (status {status}): {message:?}")]
     Api {
         status: u16,
@@ -16,4 +99,151 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Whether the same call may succeed if tried again later, possibly on
+    /// another host. Configuration, validation and most 4xx errors are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            Error::Timeout(_)
+            | Error::RateLimited { .. }
+            | Error::AllHostsFailed { .. } => true,
+            Error::BulkFailed { failures } => failures.iter().any(|f| f.error.is_retryable()),
+            Error::Api { status, .. } => *status >= 500,
+            Error::Coalesced(inner) => inner.is_retryable(),
+            Error::Serde(_)
+            | Error::Io(_)
+            | Error::RuleFile { .. }
+            | Error::MissingResult { .. }
+            | Error::ResultCountMismatch { .. }
+            | Error::ForeignBatchHandle { .. }
+            | Error::Cache(_)
+            | Error::InvalidHeader { .. }
+            | Error::InvalidHost(_)
+            | Error::Validation { .. }
+            | Error::Unauthorized { .. }
+            | Error::NotFound { .. }
+            // The caller's own deadline, waiting longer is their call
+            | Error::TaskTimeout { .. }
+            | Error::LoaderStopped => false,
+        }
+    }
+
+    /// Maps a non-2xx response to the matching variant.
+    pub(crate) fn from_response(
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    ) -> Self {
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| {
+                v.get("message")
+                    .and_then(|m| m.as_str())
+                    .map(|s| s.to_string())
+            });
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Unauthorized {
+                status: status.as_u16(),
+                message,
+                body,
+            },
+            StatusCode::NOT_FOUND => Error::NotFound { message, body },
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after,
+                message,
+                body,
+            },
+            _ => Error::Api {
+                status: status.as_u16(),
+                message,
+                body,
+            },
+        }
+    }
+}
+
+/// One failed attempt of a call, kept in [`Error::AllHostsFailed`].
+#[derive(Debug)]
+pub struct HostAttempt {
+    pub host: String,
+    pub error: Error,
+}
+
+/// A problem found in a request before it is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Position of the offending request in the submitted batch, if any.
+    pub index: Option<usize>,
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            index: None,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "requests[{index}].{}: {}", self.field, self.message),
+            None => write!(f, "{}: {}", self.field, self.message),
+        }
+    }
+}
+
+fn join(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn last_cause(attempts: &[HostAttempt]) -> String {
+    attempts
+        .last()
+        .map(|a| format!(", last on {}: {}", a.host, a.error))
+        .unwrap_or_default()
+}
+
+fn first_chunk_failure(failures: &[ChunkFailure]) -> String {
+    failures
+        .first()
+        .map(|f| {
+            format!(
+                ", first on requests {}..{}: {}",
+                f.requests.start, f.requests.end, f.error
+            )
+        })
+        .unwrap_or_default()
+}
+
+impl From<crate::filters::UnsupportedFilter> for Error {
+    fn from(err: crate::filters::UnsupportedFilter) -> Self {
+        Error::Validation {
+            errors: vec![ValidationError::new("filters", err.message)],
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
+
+// Results are matched to requests by position, so any other count than one
+// per request misaligns them. A short answer reports the first request left
+// without a result.
+pub(crate) fn check_result_count(expected: usize, got: usize) -> Result<()> {
+    if got < expected {
+        Err(Error::MissingResult { index: got })
+    } else if got > expected {
+        Err(Error::ResultCountMismatch { expected, got })
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,842 @@
+use crate::params::FilterList;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not};
+use std::str::FromStr;
+
+/// A typed `filters` expression, parsed from Algolia's filter syntax by
+/// `FromStr` and rendered back by [`Filter::try_to_string`]:
+///
+/// ```
+/// use algolia_recommend_rs::filters::{Filter, NumericOp};
+///
+/// let filter = Filter::facet("brand", "Acme Corp")
+///     & (Filter::numeric("price", NumericOp::Lt, 50) | Filter::numeric("rating", NumericOp::Gte, 4))
+///     & !Filter::facet("color", "red");
+/// assert_eq!(
+///     filter.try_to_string().unwrap(),
+///     r#"brand:"Acme Corp" AND (price < 50 OR rating >= 4) AND NOT color:red"#
+/// );
+/// assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+/// ```
+///
+/// The API only accepts ANDs of OR groups, with NOT in front of single
+/// filters. [`Filter::try_to_string`] applies De Morgan's laws, so
+/// `NOT (a:1 OR b:2)` becomes `NOT a:1 AND NOT b:2`, and rejects the rest:
+/// ANDs nested in an OR, empty groups, OR groups mixing facet, tag and
+/// numeric filters, and NaN or infinite numeric values. `Display` renders the
+/// expression as written, unchecked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Facet {
+        attribute: String,
+        value: String,
+        /// Only meaningful inside optional filters, see `<score=N>` in the docs.
+        score: Option<u32>,
+    },
+    Numeric {
+        attribute: String,
+        op: NumericOp,
+        value: f64,
+    },
+    Range {
+        attribute: String,
+        from: f64,
+        to: f64,
+    },
+    Tag(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericOp {
+    Lt,
+    Lte,
+    Eq,
+    Ne,
+    Gte,
+    Gt,
+}
+
+impl NumericOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NumericOp::Lt => "<",
+            NumericOp::Lte => "<=",
+            NumericOp::Eq => "=",
+            NumericOp::Ne => "!=",
+            NumericOp::Gte => ">=",
+            NumericOp::Gt => ">",
+        }
+    }
+}
+
+const TAGS_ATTRIBUTE: &str = "_tags";
+
+impl Filter {
+    pub fn facet(attribute: impl Into<String>, value: impl Into<String>) -> Self {
+        Filter::Facet {
+            attribute: attribute.into(),
+            value: value.into(),
+            score: None,
+        }
+    }
+
+    pub fn numeric(attribute: impl Into<String>, op: NumericOp, value: impl Into<f64>) -> Self {
+        Filter::Numeric {
+            attribute: attribute.into(),
+            op,
+            value: value.into(),
+        }
+    }
+
+    pub fn range(attribute: impl Into<String>, from: impl Into<f64>, to: impl Into<f64>) -> Self {
+        Filter::Range {
+            attribute: attribute.into(),
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Filter::Tag(tag.into())
+    }
+
+    /// Every filter must match.
+    pub fn all(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Filter::And(filters.into_iter().collect())
+    }
+
+    /// At least one filter must match.
+    pub fn any(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Filter::Or(filters.into_iter().collect())
+    }
+
+    /// Sets the score of a facet filter; other filters are returned unchanged.
+    pub fn score(self, score: u32) -> Self {
+        match self {
+            Filter::Facet {
+                attribute, value, ..
+            } => Filter::Facet {
+                attribute,
+                value,
+                score: Some(score),
+            },
+            other => other,
+        }
+    }
+
+    /// The filter in Algolia's syntax, or why the API would reject it.
+    pub fn try_to_string(&self) -> Result<String, UnsupportedFilter> {
+        let clauses = self.clauses_of(false)?;
+        for clause in &clauses {
+            if let Some((_, leaf)) = clause.iter().find(|(_, leaf)| !leaf.is_finite()) {
+                return Err(UnsupportedFilter {
+                    message: format!("numeric values must be finite: {leaf}"),
+                });
+            }
+            let kind = clause[0].1.kind();
+            if let Some((_, other)) = clause.iter().find(|(_, leaf)| leaf.kind() != kind) {
+                return Err(UnsupportedFilter {
+                    message: format!(
+                        "{} and {} filters can't be combined with OR: {}",
+                        kind,
+                        other.kind(),
+                        render_clause(clause)
+                    ),
+                });
+            }
+        }
+        Ok(render(&clauses))
+    }
+
+    // The filter as an AND of OR groups of possibly negated single filters.
+    // An AND nested in an OR is rejected rather than distributed, which
+    // could grow the filter exponentially.
+    fn clauses_of(&self, negated: bool) -> Result<Vec<Vec<(bool, &Filter)>>, UnsupportedFilter> {
+        match (self, negated) {
+            (Filter::Not(inner), _) => inner.clauses_of(!negated),
+            (Filter::And(items) | Filter::Or(items), _) if items.is_empty() => {
+                Err(UnsupportedFilter {
+                    message: String::from("empty groups match either nothing or everything"),
+                })
+            }
+            (Filter::And(items), false) | (Filter::Or(items), true) => {
+                let mut clauses = Vec::new();
+                for item in items {
+                    clauses.extend(item.clauses_of(negated)?);
+                }
+                Ok(clauses)
+            }
+            (Filter::Or(items), false) | (Filter::And(items), true) => {
+                let mut clause = Vec::new();
+                for item in items {
+                    match item.clauses_of(negated)?.as_mut_slice() {
+                        [single] => clause.append(single),
+                        _ => {
+                            return Err(UnsupportedFilter {
+                                message: format!("an AND can't be nested in an OR: {self}"),
+                            })
+                        }
+                    }
+                }
+                Ok(vec![clause])
+            }
+            (leaf, _) => Ok(vec![vec![(negated, leaf)]]),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Filter::Or(items) if items.len() > 1 => 0,
+            Filter::And(items) if items.len() > 1 => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_child(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        match self {
+            Filter::Numeric { value, .. } => value.is_finite(),
+            Filter::Range { from, to, .. } => from.is_finite() && to.is_finite(),
+            _ => true,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Filter::Facet { .. } => "facet",
+            Filter::Tag(_) => "tag",
+            _ => "numeric",
+        }
+    }
+
+    fn render_leaf(&self) -> String {
+        match self {
+            Filter::Facet {
+                attribute,
+                value,
+                score,
+            } => match score {
+                Some(score) => format!("{}:{}<score={score}>", quote(attribute), quote(value)),
+                None => format!("{}:{}", quote(attribute), quote(value)),
+            },
+            Filter::Numeric {
+                attribute,
+                op,
+                value,
+            } => format!("{} {} {value}", quote(attribute), op.as_str()),
+            Filter::Range {
+                attribute,
+                from,
+                to,
+            } => format!("{}:{from} TO {to}", quote(attribute)),
+            Filter::Tag(tag) => format!("{TAGS_ATTRIBUTE}:{}", quote(tag)),
+            Filter::And(_) | Filter::Or(_) | Filter::Not(_) => {
+                unreachable!("only single filters are leaves")
+            }
+        }
+    }
+}
+
+/// Renders the expression as written, without checking that the API
+/// accepts it, see [`Filter::try_to_string`].
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::And(items) | Filter::Or(items) => {
+                // Groups nested in an OR are always parenthesized, for
+                // readability and to keep NOT bound to a single filter
+                let (separator, min) = match self {
+                    Filter::And(_) => (" AND ", 1),
+                    _ => (" OR ", 2),
+                };
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(separator)?;
+                    }
+                    item.fmt_child(f, min)?;
+                }
+                Ok(())
+            }
+            Filter::Not(inner) => {
+                f.write_str("NOT ")?;
+                inner.fmt_child(f, 2)
+            }
+            leaf => f.write_str(&leaf.render_leaf()),
+        }
+    }
+}
+
+fn render(clauses: &[Vec<(bool, &Filter)>]) -> String {
+    clauses
+        .iter()
+        .map(|clause| {
+            if clause.len() > 1 && clauses.len() > 1 {
+                format!("({})", render_clause(clause))
+            } else {
+                render_clause(clause)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn render_clause(clause: &[(bool, &Filter)]) -> String {
+    clause
+        .iter()
+        .map(|(negated, leaf)| match negated {
+            true => format!("NOT {}", leaf.render_leaf()),
+            false => leaf.render_leaf(),
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// A [`Filter`] that has no equivalent the API accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedFilter {
+    pub message: String,
+}
+
+impl fmt::Display for UnsupportedFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for UnsupportedFilter {}
+
+impl TryFrom<Filter> for String {
+    type Error = UnsupportedFilter;
+
+    fn try_from(filter: Filter) -> Result<Self, Self::Error> {
+        filter.try_to_string()
+    }
+}
+
+impl BitAnd for Filter {
+    type Output = Filter;
+
+    fn bitand(self, rhs: Filter) -> Filter {
+        match self {
+            Filter::And(mut items) => {
+                items.push(rhs);
+                Filter::And(items)
+            }
+            lhs => Filter::And(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl BitOr for Filter {
+    type Output = Filter;
+
+    fn bitor(self, rhs: Filter) -> Filter {
+        match self {
+            Filter::Or(mut items) => {
+                items.push(rhs);
+                Filter::Or(items)
+            }
+            lhs => Filter::Or(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+// Quotes a token when it would not survive as a bare word
+fn quote(token: &str) -> String {
+    let bare = !token.is_empty()
+        && !KEYWORDS.contains(&token)
+        && token.chars().all(|c| !is_special(c) && !c.is_whitespace());
+    if bare {
+        token.to_string()
+    } else {
+        format!("\"{}\"", token.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+const KEYWORDS: [&str; 4] = ["AND", "OR", "NOT", "TO"];
+
+fn is_special(c: char) -> bool {
+    matches!(c, '(' | ')' | ':' | '<' | '>' | '=' | '!' | '"' | '\\')
+}
+
+/// A filter string that could not be parsed, with the byte offset where
+/// parsing stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FilterParseError {}
+
+impl FromStr for Filter {
+    type Err = FilterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            end: s.len(),
+            depth: 0,
+        };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some((offset, token)) => Err(FilterParseError {
+                position: *offset,
+                message: format!("unexpected {token:?}"),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    },
+                    Some((_, '"')) => {
+                        tokens.push((start, Token::Quoted(value)));
+                        break;
+                    }
+                    Some((_, other)) => value.push(other),
+                    None => {
+                        return Err(FilterParseError {
+                            position: start,
+                            message: "unterminated quoted string".to_string(),
+                        })
+                    }
+                }
+            }
+        } else if is_special(c) {
+            chars.next();
+            let next = chars.peek().map(|&(_, n)| n);
+            let op = match (c, next) {
+                ('<', Some('=')) | ('>', Some('=')) | ('!', Some('=')) => {
+                    chars.next();
+                    match c {
+                        '<' => "<=",
+                        '>' => ">=",
+                        _ => "!=",
+                    }
+                }
+                ('(', _) => "(",
+                (')', _) => ")",
+                (':', _) => ":",
+                ('<', _) => "<",
+                ('>', _) => ">",
+                ('=', _) => "=",
+                _ => {
+                    return Err(FilterParseError {
+                        position: start,
+                        message: format!("unexpected character {c:?}"),
+                    })
+                }
+            };
+            tokens.push((start, Token::Op(op)));
+        } else {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || is_special(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push((start, Token::Word(word)));
+        }
+    }
+    Ok(tokens)
+}
+
+// Nested groups and NOTs a parsed filter may have. The parser and
+// `try_to_string` recurse once per level, a deeper filter could overflow the
+// stack of the thread parsing it.
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+    // Groups and NOTs currently open
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn offset(&self) -> usize {
+        self.peek().map(|(offset, _)| *offset).unwrap_or(self.end)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, FilterParseError> {
+        Err(FilterParseError {
+            position: self.offset(),
+            message: message.into(),
+        })
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some((_, Token::Word(w))) if w == keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some((_, Token::Op(o))) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterParseError> {
+        let mut items = vec![self.and()?];
+        while self.eat_keyword("OR") {
+            items.push(self.and()?);
+        }
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            Filter::Or(items)
+        })
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterParseError> {
+        let mut items = vec![self.unary()?];
+        while self.eat_keyword("AND") {
+            items.push(self.unary()?);
+        }
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            Filter::And(items)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Filter, FilterParseError> {
+        let nested = matches!(self.peek(), Some((_, Token::Word(w))) if w == "NOT")
+            || matches!(self.peek(), Some((_, Token::Op("("))));
+        if !nested {
+            return self.term();
+        }
+        if self.depth == MAX_DEPTH {
+            return self.error(format!("filter nested deeper than {MAX_DEPTH} levels"));
+        }
+        self.depth += 1;
+        let filter = if self.eat_keyword("NOT") {
+            self.unary().map(|inner| Filter::Not(Box::new(inner)))
+        } else {
+            self.pos += 1;
+            self.or().and_then(|inner| {
+                if self.eat_op(")") {
+                    Ok(inner)
+                } else {
+                    self.error("expected ')'")
+                }
+            })
+        };
+        self.depth -= 1;
+        filter
+    }
+
+    fn operand(&mut self, what: &str) -> Result<String, FilterParseError> {
+        match self.peek() {
+            Some((_, Token::Word(w))) if !KEYWORDS.contains(&w.as_str()) => {}
+            Some((_, Token::Quoted(_))) => {}
+            _ => return self.error(format!("expected {what}")),
+        }
+        match self.next() {
+            Some((_, Token::Word(w))) | Some((_, Token::Quoted(w))) => Ok(w),
+            _ => unreachable!("checked by peek"),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, FilterParseError> {
+        let offset = self.offset();
+        let raw = self.operand("a number")?;
+        raw.parse::<f64>().map_err(|_| FilterParseError {
+            position: offset,
+            message: format!("expected a number, got {raw:?}"),
+        })
+    }
+
+    fn term(&mut self) -> Result<Filter, FilterParseError> {
+        let attribute = self.operand("an attribute")?;
+
+        let op = match self.peek() {
+            Some((_, Token::Op(op))) => *op,
+            _ => return self.error("expected ':' or a comparison operator"),
+        };
+        self.pos += 1;
+
+        let numeric_op = match op {
+            "<" => Some(NumericOp::Lt),
+            "<=" => Some(NumericOp::Lte),
+            "=" => Some(NumericOp::Eq),
+            "!=" => Some(NumericOp::Ne),
+            ">=" => Some(NumericOp::Gte),
+            ">" => Some(NumericOp::Gt),
+            _ => None,
+        };
+        if let Some(op) = numeric_op {
+            let value = self.number()?;
+            return Ok(Filter::Numeric {
+                attribute,
+                op,
+                value,
+            });
+        }
+        if op != ":" {
+            self.pos -= 1;
+            return self.error("expected ':' or a comparison operator");
+        }
+
+        let value_offset = self.offset();
+        let value = self.operand("a value")?;
+        if self.eat_keyword("TO") {
+            let from = value.parse::<f64>().map_err(|_| FilterParseError {
+                position: value_offset,
+                message: format!("expected a number, got {value:?}"),
+            })?;
+            let to = self.number()?;
+            return Ok(Filter::Range {
+                attribute,
+                from,
+                to,
+            });
+        }
+
+        let score = self.score()?;
+        if attribute == TAGS_ATTRIBUTE && score.is_none() {
+            return Ok(Filter::Tag(value));
+        }
+        Ok(Filter::Facet {
+            attribute,
+            value,
+            score,
+        })
+    }
+
+    // Optional `<score=N>` suffix of a facet filter
+    fn score(&mut self) -> Result<Option<u32>, FilterParseError> {
+        let is_score = matches!(
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)),
+            (Some((_, Token::Op("<"))), Some((_, Token::Word(w)))) if w == "score"
+        );
+        if !is_score {
+            return Ok(None);
+        }
+        self.pos += 2;
+        if !self.eat_op("=") {
+            return self.error("expected '=' after score");
+        }
+        let offset = self.offset();
+        let raw = self.operand("a score")?;
+        let score = raw.parse::<u32>().map_err(|_| FilterParseError {
+            position: offset,
+            message: format!("expected an integer score, got {raw:?}"),
+        })?;
+        if !self.eat_op(">") {
+            return self.error("expected '>' after score");
+        }
+        Ok(Some(score))
+    }
+}
+
+/// One entry of `facetFilters`, `optionalFilters` or `tagFilters`, in the
+/// array syntax (`attribute:value`, `attribute:-value`, `<score=N>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FacetFilter {
+    pub attribute: String,
+    pub value: String,
+    pub negated: bool,
+    pub score: Option<u32>,
+}
+
+impl FacetFilter {
+    pub fn eq(attribute: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            attribute: attribute.into(),
+            value: value.into(),
+            negated: false,
+            score: None,
+        }
+    }
+
+    pub fn not_eq(attribute: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            negated: true,
+            ..Self::eq(attribute, value)
+        }
+    }
+
+    /// Boost for optional filters.
+    pub fn score(mut self, score: u32) -> Self {
+        self.score = Some(score);
+        self
+    }
+}
+
+impl fmt::Display for FacetFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.attribute)?;
+        if self.negated {
+            f.write_str("-")?;
+        } else if self.value.starts_with('-') {
+            // A literal leading dash must be escaped to not mean negation
+            f.write_str("\\")?;
+        }
+        f.write_str(&self.value)?;
+        if let Some(score) = self.score {
+            write!(f, "<score={score}>")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FacetFilter {
+    type Err = FilterParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (attribute, rest) = s.split_once(':').ok_or_else(|| FilterParseError {
+            position: 0,
+            message: "expected 'attribute:value'".to_string(),
+        })?;
+
+        let (rest, score) = match rest
+            .strip_suffix('>')
+            .and_then(|r| r.rsplit_once("<score="))
+        {
+            Some((value, score)) => {
+                let score = score.parse::<u32>().map_err(|_| FilterParseError {
+                    position: attribute.len() + 1 + value.len(),
+                    message: format!("expected an integer score, got {score:?}"),
+                })?;
+                (value, Some(score))
+            }
+            None => (rest, None),
+        };
+
+        let (value, negated) = if let Some(value) = rest.strip_prefix('-') {
+            (value, true)
+        } else if rest.starts_with("\\-") {
+            (&rest[1..], false)
+        } else {
+            (rest, false)
+        };
+
+        Ok(FacetFilter {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+            negated,
+            score,
+        })
+    }
+}
+
+impl From<FacetFilter> for FilterList {
+    fn from(filter: FacetFilter) -> Self {
+        FilterList::Single(filter.to_string())
+    }
+}
+
+/// Builds the nested array form of `facetFilters` and friends, where the
+/// outer list is combined with AND and each inner list with OR:
+///
+/// ```
+/// use algolia_recommend_rs::filters::{FacetFilter, FacetFilters};
+/// use algolia_recommend_rs::FilterList;
+///
+/// let filters: FilterList = FacetFilters::new()
+///     .all(FacetFilter::eq("brand", "Acme"))
+///     .any([FacetFilter::eq("color", "red"), FacetFilter::eq("color", "blue")])
+///     .into();
+/// assert_eq!(
+///     serde_json::to_string(&filters).unwrap(),
+///     r#"["brand:Acme",["color:red","color:blue"]]"#
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FacetFilters {
+    groups: Vec<FilterList>,
+}
+
+impl FacetFilters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A filter that must match.
+    pub fn all(mut self, filter: FacetFilter) -> Self {
+        self.groups.push(filter.into());
+        self
+    }
+
+    /// A group of filters of which at least one must match.
+    pub fn any(mut self, filters: impl IntoIterator<Item = FacetFilter>) -> Self {
+        self.groups.push(FilterList::List(
+            filters.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+}
+
+impl From<FacetFilters> for FilterList {
+    fn from(filters: FacetFilters) -> Self {
+        FilterList::List(filters.groups)
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Same expiration delay as the official clients' StatefulHost
+// https://github.com/algolia/algoliasearch-client-javascript/blob/main/packages/client-common/src/transporter/createStatefulHost.ts
+pub const DEFAULT_HOST_DOWN_TTL: Duration = Duration::from_secs(2 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostState {
+    Up,
+    Down,
+    TimedOut,
+}
+
+/// Snapshot of a host as tracked by the client's retry logic.
+#[derive(Debug, Clone)]
+pub struct HostStatus {
+    pub url: String,
+    pub state: HostState,
+    pub last_update: Instant,
+    /// Consecutive timeouts, used to scale the timeout of the next attempt.
+    pub retry_count: u32,
+    /// Whether the host will be tried first on the next request, either
+    /// because it is up or because its down TTL has expired.
+    pub available: bool,
+}
+
+#[derive(Debug)]
+struct StatefulHost {
+    url: String,
+    state: HostState,
+    last_update: Instant,
+    retry_count: u32,
+}
+
+impl StatefulHost {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            state: HostState::Up,
+            last_update: Instant::now(),
+            retry_count: 0,
+        }
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.last_update.elapsed() > ttl
+    }
+
+    fn is_available(&self, ttl: Duration) -> bool {
+        self.state == HostState::Up || self.is_expired(ttl)
+    }
+
+    fn is_timed_out(&self, ttl: Duration) -> bool {
+        self.state == HostState::TimedOut && !self.is_expired(ttl)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct HostPool {
+    hosts: Mutex<Vec<StatefulHost>>,
+    cursor: AtomicUsize,
+    down_ttl: Duration,
+}
+
+impl HostPool {
+    pub(crate) fn new(urls: Vec<String>, down_ttl: Duration) -> Self {
+        Self {
+            hosts: Mutex::new(urls.into_iter().map(StatefulHost::new).collect()),
+            cursor: AtomicUsize::new(0),
+            down_ttl,
+        }
+    }
+
+    /// Hosts to try for one request, in order: available hosts starting from a
+    /// rotating cursor, then hosts that recently timed out. When nothing is
+    /// usable every host is reset to up, like the official transporters do.
+    pub(crate) fn plan(&self) -> Vec<(String, u32)> {
+        let mut hosts = self.lock();
+        let total = hosts.len();
+        if total == 0 {
+            return Vec::new();
+        }
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed) % total;
+        let rotated = (0..total).map(|i| (start + i) % total);
+
+        let mut up = Vec::new();
+        let mut timed_out = Vec::new();
+        for idx in rotated {
+            let host = &hosts[idx];
+            if host.is_available(self.down_ttl) {
+                up.push(idx);
+            } else if host.is_timed_out(self.down_ttl) {
+                timed_out.push(idx);
+            }
+        }
+        up.extend(timed_out);
+
+        if up.is_empty() {
+            for host in hosts.iter_mut() {
+                *host = StatefulHost::new(std::mem::take(&mut host.url));
+            }
+            up = (0..total).map(|i| (start + i) % total).collect();
+        }
+
+        up.into_iter()
+            .map(|idx| (hosts[idx].url.clone(), hosts[idx].retry_count))
+            .collect()
+    }
+
+    pub(crate) fn mark_up(&self, url: &str) {
+        self.update(url, |host| {
+            host.state = HostState::Up;
+            host.retry_count = 0;
+        });
+    }
+
+    pub(crate) fn mark_down(&self, url: &str) {
+        self.update(url, |host| host.state = HostState::Down);
+    }
+
+    pub(crate) fn mark_timed_out(&self, url: &str) {
+        self.update(url, |host| {
+            host.state = HostState::TimedOut;
+            host.retry_count = host.retry_count.saturating_add(1);
+        });
+    }
+
+    pub(crate) fn statuses(&self) -> Vec<HostStatus> {
+        self.lock()
+            .iter()
+            .map(|host| HostStatus {
+                url: host.url.clone(),
+                state: host.state,
+                last_update: host.last_update,
+                retry_count: host.retry_count,
+                available: host.is_available(self.down_ttl),
+            })
+            .collect()
+    }
+
+    fn update(&self, url: &str, f: impl FnOnce(&mut StatefulHost)) {
+        let mut hosts = self.lock();
+        if let Some(host) = hosts.iter_mut().find(|h| h.url == url) {
+            f(host);
+            host.last_update = Instant::now();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<StatefulHost>> {
+        // The host list stays consistent even if a holder panicked.
+        self.hosts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -1,7 +1,35 @@
+pub mod batch;
+pub mod bulk;
+pub mod cache;
 pub mod client;
+mod coalesce;
 pub mod error;
+pub mod filters;
+pub mod host;
+pub mod loader;
 pub mod models;
+pub mod options;
+pub mod params;
+#[cfg(feature = "redis")]
+pub mod redis_cache;
+pub mod retry;
+pub mod rules;
+pub mod secret;
+pub mod sync;
+pub mod task;
 
-pub use client::RecommendClient;
-pub use error::Error;
+pub use batch::{BatchHandle, BatchResponse, RecommendBatch};
+pub use bulk::{BulkOptions, BulkResponse};
+pub use cache::{CacheConfig, CacheStats, MemoryCache, RecommendCache};
+pub use client::{RecommendClient, RecommendClientBuilder};
+pub use error::{Error, HostAttempt, ValidationError};
+pub use host::{HostState, HostStatus};
+pub use loader::{LoaderOptions, RecommendLoader};
 pub use models::*;
+pub use options::RequestOptions;
+pub use params::{FilterList, SearchParams};
+pub use retry::{ExponentialBackoff, RetryPolicy};
+pub use rules::{Condition, Consequence, RecommendRule, SearchRulesParams};
+pub use secret::{ApiKey, Secret};
+pub use sync::{SyncOptions, SyncPlan};
+pub use task::{TaskStatus, WaitForTaskOptions};
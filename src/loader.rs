@@ -0,0 +1,160 @@
+//! Automatic batching of the requests of concurrent callers, DataLoader
+//! style: requests made within a short window are sent as one call.
+
+use crate::client::RecommendClient;
+use crate::error::{Error, Result};
+use crate::models::{RecommendRequest, RecommendResult};
+use crate::options::RequestOptions;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// When a [`RecommendLoader`] sends the requests it collected.
+#[derive(Debug, Clone)]
+pub struct LoaderOptions {
+    /// How long the first request of a batch waits for others.
+    pub window: Duration,
+    /// A full batch is sent without waiting for the end of the window.
+    pub max_batch_size: usize,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(5),
+            max_batch_size: 50,
+        }
+    }
+}
+
+impl LoaderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+}
+
+struct Job {
+    request: RecommendRequest,
+    reply: oneshot::Sender<Result<(Value, bool)>>,
+}
+
+/// Collects the requests of concurrent callers into batches, sends each
+/// batch as one call and hands every caller its own result.
+///
+/// Clones share the same batches. The background task stops once every
+/// clone is dropped.
+#[derive(Debug, Clone)]
+pub struct RecommendLoader {
+    client: RecommendClient,
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl RecommendLoader {
+    /// Recommendations for `request`, sent along with the other requests of
+    /// its batch. Validation errors only fail their own request; the failure
+    /// of a batch call reaches each of its callers as
+    /// [`Error::Coalesced`], unless the batch had only one request.
+    pub async fn load<T: serde::de::DeserializeOwned>(
+        &self,
+        request: RecommendRequest,
+    ) -> Result<RecommendResult<T>> {
+        self.client.check_requests(std::slice::from_ref(&request))?;
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(Job { request, reply })
+            .map_err(|_| Error::LoaderStopped)?;
+        let (value, stale) = result.await.map_err(|_| Error::LoaderStopped)??;
+        let mut result: RecommendResult<T> = serde_json::from_value(value)?;
+        result.stale = stale;
+        Ok(result)
+    }
+}
+
+impl RecommendClient {
+    /// A [`RecommendLoader`] sending its batches through this client, with
+    /// its cache and request coalescing. Must be called within a Tokio
+    /// runtime.
+    pub fn loader(&self, options: LoaderOptions) -> RecommendLoader {
+        self.loader_with_options(options, &RequestOptions::new())
+    }
+
+    /// Like [`RecommendClient::loader`], every batch being sent with
+    /// `request_options`.
+    pub fn loader_with_options(
+        &self,
+        options: LoaderOptions,
+        request_options: &RequestOptions,
+    ) -> RecommendLoader {
+        let (jobs, queue) = mpsc::unbounded_channel();
+        tokio::spawn(collect(
+            self.clone(),
+            options,
+            request_options.clone(),
+            queue,
+        ));
+        RecommendLoader {
+            client: self.clone(),
+            jobs,
+        }
+    }
+}
+
+async fn collect(
+    client: RecommendClient,
+    options: LoaderOptions,
+    request_options: RequestOptions,
+    mut queue: mpsc::UnboundedReceiver<Job>,
+) {
+    let max_batch_size = options.max_batch_size.max(1);
+    while let Some(first) = queue.recv().await {
+        let mut batch = vec![first];
+        let deadline = tokio::time::Instant::now() + options.window;
+        while batch.len() < max_batch_size {
+            match tokio::time::timeout_at(deadline, queue.recv()).await {
+                Ok(Some(job)) => batch.push(job),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        // The next batch is collected while this one is in flight
+        tokio::spawn(dispatch(client.clone(), request_options.clone(), batch));
+    }
+}
+
+async fn dispatch(client: RecommendClient, options: RequestOptions, batch: Vec<Job>) {
+    let (requests, replies): (Vec<_>, Vec<_>) = batch
+        .into_iter()
+        .map(|job| (job.request, job.reply))
+        .unzip();
+    match client.fetch_values(&requests, &options).await {
+        Ok(results) => {
+            for (reply, result) in replies.into_iter().zip(results) {
+                // The caller may have stopped waiting
+                let _ = reply.send(Ok(result));
+            }
+        }
+        Err(err) if replies.len() == 1 => {
+            let _ = replies
+                .into_iter()
+                .next()
+                .expect("one reply")
+                .send(Err(err));
+        }
+        Err(err) => {
+            let shared = Arc::new(err);
+            for reply in replies {
+                let _ = reply.send(Err(Error::Coalesced(shared.clone())));
+            }
+        }
+    }
+}
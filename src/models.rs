@@ -1,14 +1,133 @@
+use crate::error::ValidationError;
+use crate::params::{FilterList, SearchParams};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
+// Limits documented for the Recommend API request body
+pub const MAX_THRESHOLD: i32 = 100;
+pub const MAX_RECOMMENDATIONS: u32 = 30;
+
+// Serialized through its API name so that models this crate does not know
+// about yet survive a round trip as `Other`. Compared by that name too, so
+// an `Other` holding a known name is the same model as its variant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum Model {
     BoughtTogether,
     RelatedProducts,
     TrendingItems,
     TrendingFacets,
     LookingSimilar,
+    RecommendedForYou,
+    Other(String),
+}
+
+impl Model {
+    /// The model name used by the API, e.g. `bought-together`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Model::BoughtTogether => "bought-together",
+            Model::RelatedProducts => "related-products",
+            Model::TrendingItems => "trending-items",
+            Model::TrendingFacets => "trending-facets",
+            Model::LookingSimilar => "looking-similar",
+            Model::RecommendedForYou => "recommended-for-you",
+            Model::Other(name) => name,
+        }
+    }
+
+    /// Models whose requests may carry `fallbackParameters`.
+    pub fn accepts_fallback_parameters(&self) -> bool {
+        matches!(
+            *self.canonical(),
+            Model::RelatedProducts
+                | Model::LookingSimilar
+                | Model::TrendingItems
+                | Model::RecommendedForYou
+        )
+    }
+
+    /// Models that recommend items related to a given `objectID`.
+    pub fn requires_object_id(&self) -> bool {
+        matches!(
+            *self.canonical(),
+            Model::BoughtTogether | Model::RelatedProducts | Model::LookingSimilar
+        )
+    }
+
+    /// Whether this crate knows the model, whichever variant holds it.
+    pub fn is_known(&self) -> bool {
+        !matches!(*self.canonical(), Model::Other(_))
+    }
+
+    // The variant for the name, for an `Other` built by hand
+    fn canonical(&self) -> Cow<'_, Model> {
+        match self {
+            Model::Other(name) => Cow::Owned(Model::from(name.as_str())),
+            known => Cow::Borrowed(known),
+        }
+    }
+}
+
+impl PartialEq for Model {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Model {}
+
+impl Hash for Model {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl From<&str> for Model {
+    fn from(name: &str) -> Self {
+        match name {
+            "bought-together" => Model::BoughtTogether,
+            "related-products" => Model::RelatedProducts,
+            "trending-items" => Model::TrendingItems,
+            "trending-facets" => Model::TrendingFacets,
+            "looking-similar" => Model::LookingSimilar,
+            "recommended-for-you" => Model::RecommendedForYou,
+            other => Model::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Model {
+    fn from(name: String) -> Self {
+        match Model::from(name.as_str()) {
+            Model::Other(_) => Model::Other(name),
+            known => known,
+        }
+    }
+}
+
+impl From<Model> for String {
+    fn from(model: Model) -> Self {
+        match model {
+            Model::Other(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Model {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Model::from(s))
+    }
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,15 +148,24 @@ pub struct RecommendRequest {
     #[serde(rename = "maxRecommendations")]
     pub max_recommendations: Option<u32>,
 
-    // Specific to trending facets (not used here but kept for forward-compat)
+    // Facet attribute of trending-facets, or the attribute that scopes
+    // trending-items to `facet_value`
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "facetName")]
     pub facet_name: Option<String>,
 
-    // Arbitrary query parameters passthrough (see docs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "facetValue")]
+    pub facet_value: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "queryParameters")]
-    pub query_parameters: Option<Value>,
+    pub query_parameters: Option<SearchParams>,
+
+    // Search parameters used to fill in when the model returns too few hits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fallbackParameters")]
+    pub fallback_parameters: Option<SearchParams>,
 }
 
 impl RecommendRequest {
@@ -49,7 +177,9 @@ impl RecommendRequest {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         }
     }
 
@@ -61,7 +191,9 @@ impl RecommendRequest {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         }
     }
 
@@ -73,7 +205,28 @@ impl RecommendRequest {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
+        }
+    }
+
+    /// Trending items within one facet value, e.g. `category` = `shoes`.
+    pub fn trending_items_for_facet(
+        index_name: impl Into<String>,
+        facet_name: impl Into<String>,
+        facet_value: impl Into<String>,
+    ) -> Self {
+        Self {
+            index_name: index_name.into(),
+            model: Model::TrendingItems,
+            object_id: None,
+            threshold: 0,
+            max_recommendations: None,
+            facet_name: Some(facet_name.into()),
+            facet_value: Some(facet_value.into()),
+            query_parameters: None,
+            fallback_parameters: None,
         }
     }
 
@@ -85,9 +238,160 @@ impl RecommendRequest {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
+            query_parameters: None,
+            fallback_parameters: None,
+        }
+    }
+
+    /// Personalized recommendations for the user identified by `user_token`,
+    /// sent as `queryParameters.userToken`.
+    pub fn recommended_for_you(
+        index_name: impl Into<String>,
+        user_token: impl Into<String>,
+    ) -> Self {
+        Self {
+            index_name: index_name.into(),
+            model: Model::RecommendedForYou,
+            object_id: None,
+            threshold: 0,
+            max_recommendations: None,
+            facet_name: None,
+            facet_value: None,
+            query_parameters: Some(SearchParams::new().user_token(user_token)),
+            fallback_parameters: None,
+        }
+    }
+
+    /// Checks model-specific required fields and API limits, returning every
+    /// problem found rather than only the first one.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        validate_common(
+            &self.index_name,
+            self.threshold,
+            self.max_recommendations,
+            &mut errors,
+        );
+
+        if self.model.requires_object_id() && self.object_id.as_deref().unwrap_or("").is_empty() {
+            errors.push(ValidationError::new(
+                "objectID",
+                format!("is required for model {}", self.model),
+            ));
+        }
+        if self.model == Model::RecommendedForYou
+            && self
+                .query_parameters
+                .as_ref()
+                .and_then(|p| p.user_token.as_deref())
+                .unwrap_or("")
+                .is_empty()
+        {
+            errors.push(ValidationError::new(
+                "queryParameters.userToken",
+                "is required for model recommended-for-you",
+            ));
+        }
+        // Unknown models are left for the API to judge
+        let known_model = self.model.is_known();
+        if self.fallback_parameters.is_some()
+            && known_model
+            && !self.model.accepts_fallback_parameters()
+        {
+            errors.push(ValidationError::new(
+                "fallbackParameters",
+                format!("is not supported by model {}", self.model),
+            ));
+        }
+        if self.model == Model::TrendingFacets
+            && self.facet_name.as_deref().unwrap_or("").is_empty()
+        {
+            errors.push(ValidationError::new(
+                "facetName",
+                "is required for model trending-facets",
+            ));
+        }
+
+        if self.model == Model::TrendingItems
+            && self.facet_name.is_some() != self.facet_value.is_some()
+        {
+            errors.push(ValidationError::new(
+                "facetValue",
+                "facetName and facetValue must be set together for model trending-items",
+            ));
+        }
+        if self.facet_value.is_some() && known_model && self.model != Model::TrendingItems {
+            errors.push(ValidationError::new(
+                "facetValue",
+                format!("is not supported by model {}", self.model),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Start a type-checked builder, see [`RecommendRequestBuilder`].
+    pub fn builder(index_name: impl Into<String>) -> RecommendRequestBuilder<NoModel> {
+        RecommendRequestBuilder {
+            index_name: index_name.into(),
+            threshold: 0,
+            max_recommendations: None,
             query_parameters: None,
+            fallback_parameters: None,
+            state: NoModel,
         }
     }
+
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max(mut self, max_recommendations: u32) -> Self {
+        self.max_recommendations = Some(max_recommendations);
+        self
+    }
+
+    pub fn query_params(mut self, query_parameters: SearchParams) -> Self {
+        self.query_parameters = Some(query_parameters);
+        self
+    }
+
+    /// Search parameters used when the model returns too few recommendations.
+    pub fn fallback_params(mut self, fallback_parameters: SearchParams) -> Self {
+        self.fallback_parameters = Some(fallback_parameters);
+        self
+    }
+
+    /// Sets `queryParameters.filters`, e.g. from
+    /// [`Filter::try_to_string`](crate::filters::Filter::try_to_string).
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .filters = Some(filters.into());
+        self
+    }
+
+    /// Sets `queryParameters.facetFilters`, e.g. from
+    /// [`FacetFilters`](crate::filters::FacetFilters).
+    pub fn facet_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .facet_filters = Some(filters.into());
+        self
+    }
+
+    pub fn optional_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .optional_filters = Some(filters.into());
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,7 +411,7 @@ pub struct TrendingFacetsRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "queryParameters")]
-    pub query_parameters: Option<Value>,
+    pub query_parameters: Option<SearchParams>,
 }
 
 impl TrendingFacetsRequest {
@@ -121,6 +425,321 @@ impl TrendingFacetsRequest {
             query_parameters: None,
         }
     }
+
+    /// Same checks as [`RecommendRequest::validate`], for trending facets.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        if self.model != Model::TrendingFacets {
+            errors.push(ValidationError::new(
+                "model",
+                format!(
+                    "all requests must use model=trending-facets, got {}",
+                    self.model
+                ),
+            ));
+        }
+        validate_common(
+            &self.index_name,
+            self.threshold,
+            self.max_recommendations,
+            &mut errors,
+        );
+        if self.facet_name.is_empty() {
+            errors.push(ValidationError::new("facetName", "must not be empty"));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Start a type-checked builder, see [`TrendingFacetsRequestBuilder`].
+    pub fn builder(index_name: impl Into<String>) -> TrendingFacetsRequestBuilder<NoFacetName> {
+        TrendingFacetsRequestBuilder {
+            index_name: index_name.into(),
+            threshold: 0,
+            max_recommendations: None,
+            query_parameters: None,
+            state: NoFacetName,
+        }
+    }
+
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max(mut self, max_recommendations: u32) -> Self {
+        self.max_recommendations = Some(max_recommendations);
+        self
+    }
+
+    pub fn query_params(mut self, query_parameters: SearchParams) -> Self {
+        self.query_parameters = Some(query_parameters);
+        self
+    }
+
+    /// Sets `queryParameters.filters`, e.g. from
+    /// [`Filter::try_to_string`](crate::filters::Filter::try_to_string).
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .filters = Some(filters.into());
+        self
+    }
+
+    /// Sets `queryParameters.facetFilters`, e.g. from
+    /// [`FacetFilters`](crate::filters::FacetFilters).
+    pub fn facet_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .facet_filters = Some(filters.into());
+        self
+    }
+
+    pub fn optional_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .optional_filters = Some(filters.into());
+        self
+    }
+}
+
+/// Builder for [`RecommendRequest`] that only offers `build()` once a model
+/// has been picked, and only lets object-ID models be picked with an object ID:
+///
+/// ```
+/// use algolia_recommend_rs::RecommendRequest;
+///
+/// let request = RecommendRequest::builder("products")
+///     .threshold(40)
+///     .max(10)
+///     .bought_together("obj-1")
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecommendRequestBuilder<S> {
+    index_name: String,
+    threshold: i32,
+    max_recommendations: Option<u32>,
+    query_parameters: Option<SearchParams>,
+    fallback_parameters: Option<SearchParams>,
+    state: S,
+}
+
+/// Type state of a [`RecommendRequestBuilder`] without a model.
+#[derive(Debug, Clone)]
+pub struct NoModel;
+
+/// Type state of a [`RecommendRequestBuilder`] ready to be built.
+#[derive(Debug, Clone)]
+pub struct ModelSelected {
+    model: Model,
+    object_id: Option<String>,
+    facet: Option<(String, String)>,
+}
+
+impl<S> RecommendRequestBuilder<S> {
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max(mut self, max_recommendations: u32) -> Self {
+        self.max_recommendations = Some(max_recommendations);
+        self
+    }
+
+    pub fn query_params(mut self, query_parameters: SearchParams) -> Self {
+        self.query_parameters = Some(query_parameters);
+        self
+    }
+
+    /// Search parameters used when the model returns too few recommendations.
+    pub fn fallback_params(mut self, fallback_parameters: SearchParams) -> Self {
+        self.fallback_parameters = Some(fallback_parameters);
+        self
+    }
+
+    fn with_model(
+        self,
+        model: Model,
+        object_id: Option<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_state(ModelSelected {
+            model,
+            object_id,
+            facet: None,
+        })
+    }
+
+    fn with_state(self, state: ModelSelected) -> RecommendRequestBuilder<ModelSelected> {
+        RecommendRequestBuilder {
+            index_name: self.index_name,
+            threshold: self.threshold,
+            max_recommendations: self.max_recommendations,
+            query_parameters: self.query_parameters,
+            fallback_parameters: self.fallback_parameters,
+            state,
+        }
+    }
+}
+
+impl RecommendRequestBuilder<NoModel> {
+    pub fn bought_together(
+        self,
+        object_id: impl Into<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_model(Model::BoughtTogether, Some(object_id.into()))
+    }
+
+    pub fn related_products(
+        self,
+        object_id: impl Into<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_model(Model::RelatedProducts, Some(object_id.into()))
+    }
+
+    pub fn looking_similar(
+        self,
+        object_id: impl Into<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_model(Model::LookingSimilar, Some(object_id.into()))
+    }
+
+    pub fn trending_items(self) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_model(Model::TrendingItems, None)
+    }
+
+    pub fn trending_items_for_facet(
+        self,
+        facet_name: impl Into<String>,
+        facet_value: impl Into<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.with_state(ModelSelected {
+            model: Model::TrendingItems,
+            object_id: None,
+            facet: Some((facet_name.into(), facet_value.into())),
+        })
+    }
+
+    /// Keeps any query parameters already set and adds the user token.
+    pub fn recommended_for_you(
+        mut self,
+        user_token: impl Into<String>,
+    ) -> RecommendRequestBuilder<ModelSelected> {
+        self.query_parameters
+            .get_or_insert_with(SearchParams::default)
+            .user_token = Some(user_token.into());
+        self.with_model(Model::RecommendedForYou, None)
+    }
+}
+
+impl RecommendRequestBuilder<ModelSelected> {
+    pub fn build(self) -> RecommendRequest {
+        RecommendRequest {
+            index_name: self.index_name,
+            model: self.state.model,
+            object_id: self.state.object_id,
+            threshold: self.threshold,
+            max_recommendations: self.max_recommendations,
+            facet_name: self.state.facet.as_ref().map(|(name, _)| name.clone()),
+            facet_value: self.state.facet.map(|(_, value)| value),
+            query_parameters: self.query_parameters,
+            fallback_parameters: self.fallback_parameters,
+        }
+    }
+}
+
+/// Builder for [`TrendingFacetsRequest`] that requires a facet name before
+/// `build()` is available.
+#[derive(Debug, Clone)]
+pub struct TrendingFacetsRequestBuilder<S> {
+    index_name: String,
+    threshold: i32,
+    max_recommendations: Option<u32>,
+    query_parameters: Option<SearchParams>,
+    state: S,
+}
+
+/// Type state of a [`TrendingFacetsRequestBuilder`] without a facet name.
+#[derive(Debug, Clone)]
+pub struct NoFacetName;
+
+/// Type state of a [`TrendingFacetsRequestBuilder`] ready to be built.
+#[derive(Debug, Clone)]
+pub struct FacetNameSelected(String);
+
+impl<S> TrendingFacetsRequestBuilder<S> {
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max(mut self, max_recommendations: u32) -> Self {
+        self.max_recommendations = Some(max_recommendations);
+        self
+    }
+
+    pub fn query_params(mut self, query_parameters: SearchParams) -> Self {
+        self.query_parameters = Some(query_parameters);
+        self
+    }
+}
+
+impl TrendingFacetsRequestBuilder<NoFacetName> {
+    pub fn facet_name(
+        self,
+        facet_name: impl Into<String>,
+    ) -> TrendingFacetsRequestBuilder<FacetNameSelected> {
+        TrendingFacetsRequestBuilder {
+            index_name: self.index_name,
+            threshold: self.threshold,
+            max_recommendations: self.max_recommendations,
+            query_parameters: self.query_parameters,
+            state: FacetNameSelected(facet_name.into()),
+        }
+    }
+}
+
+impl TrendingFacetsRequestBuilder<FacetNameSelected> {
+    pub fn build(self) -> TrendingFacetsRequest {
+        TrendingFacetsRequest {
+            model: Model::TrendingFacets,
+            index_name: self.index_name,
+            facet_name: self.state.0,
+            threshold: self.threshold,
+            max_recommendations: self.max_recommendations,
+            query_parameters: self.query_parameters,
+        }
+    }
+}
+
+fn validate_common(
+    index_name: &str,
+    threshold: i32,
+    max_recommendations: Option<u32>,
+    errors: &mut Vec<ValidationError>,
+) {
+    if index_name.is_empty() {
+        errors.push(ValidationError::new("indexName", "must not be empty"));
+    }
+    if !(0..=MAX_THRESHOLD).contains(&threshold) {
+        errors.push(ValidationError::new(
+            "threshold",
+            format!("must be between 0 and {MAX_THRESHOLD}, got {threshold}"),
+        ));
+    }
+    if let Some(max) = max_recommendations {
+        if !(1..=MAX_RECOMMENDATIONS).contains(&max) {
+            errors.push(ValidationError::new(
+                "maxRecommendations",
+                format!("must be between 1 and {MAX_RECOMMENDATIONS}, got {max}"),
+            ));
+        }
+    }
 }
 
 // Generic recommendations response (excluding trending-facets)
@@ -159,6 +778,10 @@ pub struct RecommendResult<T> {
     pub query_id: Option<String>,
     #[serde(default)]
     pub message: Option<String>,
+    /// Served from the cache past its TTL, see
+    /// [`RecommendClientBuilder::stale_while_revalidate`](crate::RecommendClientBuilder::stale_while_revalidate).
+    #[serde(skip)]
+    pub stale: bool,
 }
 
 // Trending facets response structures
@@ -0,0 +1,79 @@
+use crate::cache::digest;
+use crate::error::Result;
+use crate::models::RecommendRequest;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Per-call metadata, accepted by the `*_with_options` methods of
+/// [`RecommendClient`](crate::RecommendClient).
+///
+/// Header values are validated when the call is made; an invalid one fails
+/// the call with [`Error::InvalidHeader`](crate::Error::InvalidHeader).
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) query_params: Vec<(String, String)>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) bypass_cache: bool,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a header to the call. Headers set here are sent in addition to the
+    /// client's and replace them when the name is the same.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// IP of the end user, when calling Algolia on their behalf from a backend.
+    pub fn forwarded_for(self, ip: impl Into<String>) -> Self {
+        self.header("x-forwarded-for", ip)
+    }
+
+    /// Identifies the end user for analytics and personalization.
+    pub fn user_token(self, token: impl Into<String>) -> Self {
+        self.header("x-algolia-usertoken", token)
+    }
+
+    /// Append a query parameter to the request URL.
+    pub fn query_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query_params.push((key.into(), value.into()));
+        self
+    }
+
+    /// Timeout of each HTTP attempt of this call, instead of the client's.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Skip the client's cache for this call: everything is sent, and the
+    /// fresh results replace the cached ones.
+    pub fn bypass_cache(mut self, bypass: bool) -> Self {
+        self.bypass_cache = bypass;
+        self
+    }
+
+    // Identifies `request` sent with these options, for caching and
+    // coalescing. Headers and query parameters are part of it: another user
+    // token may well get other recommendations. They are hashed, since a
+    // per-call API key must not end up in a cache backend.
+    pub(crate) fn request_key(&self, request: &RecommendRequest) -> Result<String> {
+        // Header names are case-insensitive and the last value set is sent
+        let headers: BTreeMap<String, &str> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.as_str()))
+            .collect();
+        let metadata = format!("{:?}|{:?}", headers, self.query_params);
+        Ok(format!(
+            "{}|{}",
+            serde_json::to_string(request)?,
+            digest(metadata.as_bytes())
+        ))
+    }
+}
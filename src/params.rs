@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Search parameters accepted by Recommend in `queryParameters`.
+///
+/// Only the documented parameters have typed fields, so a misspelled setter
+/// fails to compile instead of being silently ignored by the API. Anything
+/// else can still be sent through [`SearchParams::extra`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facet_filters: Option<FilterList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optional_filters: Option<FilterList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric_filters: Option<FilterList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_filters: Option<FilterList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sum_or_filters_scores: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes_to_retrieve: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics_tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub click_analytics: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_contexts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_rules: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_personalization: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub personalization_impact: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub get_ranking_info: Option<bool>,
+
+    // Escape hatch for parameters without a typed field; deserializing also
+    // lands unknown keys here so that nothing is lost on a round trip
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SearchParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.filters = Some(filters.into());
+        self
+    }
+
+    pub fn facet_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.facet_filters = Some(filters.into());
+        self
+    }
+
+    pub fn optional_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.optional_filters = Some(filters.into());
+        self
+    }
+
+    pub fn numeric_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.numeric_filters = Some(filters.into());
+        self
+    }
+
+    pub fn tag_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.tag_filters = Some(filters.into());
+        self
+    }
+
+    pub fn sum_or_filters_scores(mut self, enabled: bool) -> Self {
+        self.sum_or_filters_scores = Some(enabled);
+        self
+    }
+
+    pub fn facets<I, S>(mut self, facets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.facets = Some(facets.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn attributes_to_retrieve<I, S>(mut self, attributes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.attributes_to_retrieve = Some(attributes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn analytics(mut self, enabled: bool) -> Self {
+        self.analytics = Some(enabled);
+        self
+    }
+
+    pub fn analytics_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.analytics_tags = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn click_analytics(mut self, enabled: bool) -> Self {
+        self.click_analytics = Some(enabled);
+        self
+    }
+
+    pub fn user_token(mut self, token: impl Into<String>) -> Self {
+        self.user_token = Some(token.into());
+        self
+    }
+
+    pub fn rule_contexts<I, S>(mut self, contexts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.rule_contexts = Some(contexts.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn enable_rules(mut self, enabled: bool) -> Self {
+        self.enable_rules = Some(enabled);
+        self
+    }
+
+    pub fn enable_personalization(mut self, enabled: bool) -> Self {
+        self.enable_personalization = Some(enabled);
+        self
+    }
+
+    pub fn personalization_impact(mut self, impact: u32) -> Self {
+        self.personalization_impact = Some(impact);
+        self
+    }
+
+    pub fn get_ranking_info(mut self, enabled: bool) -> Self {
+        self.get_ranking_info = Some(enabled);
+        self
+    }
+
+    /// Set a parameter that has no typed field, by its API name.
+    pub fn extra(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A `facetFilters`, `optionalFilters`, `numericFilters` or `tagFilters` value:
+/// a single filter, or a list whose nested lists are combined with OR.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterList {
+    Single(String),
+    List(Vec<FilterList>),
+}
+
+impl From<&str> for FilterList {
+    fn from(filter: &str) -> Self {
+        FilterList::Single(filter.to_string())
+    }
+}
+
+impl From<String> for FilterList {
+    fn from(filter: String) -> Self {
+        FilterList::Single(filter)
+    }
+}
+
+impl<T: Into<FilterList>> From<Vec<T>> for FilterList {
+    fn from(filters: Vec<T>) -> Self {
+        FilterList::List(filters.into_iter().map(Into::into).collect())
+    }
+}
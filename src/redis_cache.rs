@@ -0,0 +1,343 @@
+//! A [`RecommendCache`] stored in Redis, or anything speaking its protocol.
+//!
+//! Only `GET`, `MGET`, `SET ... PX` and `DEL` are used, over one connection
+//! that is reopened after a failure. Until then, commands fail right away for
+//! a backoff period rather than each waiting for the server.
+//!
+//! The requests of a call are looked up with a single `MGET` and their
+//! results stored with pipelined `SET`s, one round trip each whatever the
+//! size of the batch.
+
+use crate::cache::RecommendCache;
+use crate::error::{Error, Result};
+use crate::secret::Secret;
+use futures_util::future::BoxFuture;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+// Far more than any cached result. Bounds what a corrupt or hostile stream
+// can make the client allocate for one value.
+const MAX_BULK_LEN: i64 = 64 * 1024 * 1024;
+
+pub struct RedisCache {
+    addr: String,
+    password: Option<Secret>,
+    database: Option<u32>,
+    key_prefix: String,
+    timeout: Duration,
+    backoff: Duration,
+    // Set after a failure, commands fail without trying until then
+    down_until: std::sync::Mutex<Option<Instant>>,
+    conn: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+// Written by hand to leave out the connection
+impl fmt::Debug for RedisCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisCache")
+            .field("addr", &self.addr)
+            .field("password", &self.password)
+            .field("database", &self.database)
+            .field("key_prefix", &self.key_prefix)
+            .field("timeout", &self.timeout)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RedisCache {
+    /// `addr` is a `host:port` pair; nothing is connected until first use.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            password: None,
+            database: None,
+            key_prefix: String::from("algolia-recommend:"),
+            timeout: Duration::from_millis(500),
+            backoff: Duration::from_secs(1),
+            down_until: std::sync::Mutex::new(None),
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Sent with `AUTH` on every new connection.
+    pub fn password(mut self, password: impl Into<Secret>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Selected with `SELECT` on every new connection.
+    pub fn database(mut self, database: u32) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Prepended to every key, `algolia-recommend:` by default.
+    pub fn key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = prefix.into();
+        self
+    }
+
+    /// Limit for one command, including waiting for the connection and
+    /// connecting. A slow cache should not hold up recommendations for long.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long commands fail right away after a failure, 1s by default.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    async fn command(&self, args: &[&[u8]]) -> Result<Reply> {
+        let mut replies = self.pipeline(&[args.to_vec()]).await?;
+        Ok(replies.pop().expect("one reply per command"))
+    }
+
+    // Sends `commands` in one write and reads their replies, in order. An
+    // error reply to any of them fails the whole pipeline.
+    async fn pipeline(&self, commands: &[Vec<&[u8]>]) -> Result<Vec<Reply>> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let timed_out = || Error::Cache(format!("{} timed out", self.addr));
+        self.check_backoff()?;
+        let mut conn = tokio::time::timeout_at(deadline, self.conn.lock())
+            .await
+            .map_err(|_| timed_out())?;
+        // Commands queued behind a failed one give up too
+        self.check_backoff()?;
+        let result = tokio::time::timeout_at(deadline, async {
+            if conn.is_none() {
+                *conn = Some(self.connect().await?);
+            }
+            let stream = conn.as_mut().expect("connected above");
+            round_trip(stream, commands).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(timed_out()));
+
+        // The stream may be half way through a reply, start over next time
+        if matches!(result, Err(Error::Io(_)) | Err(Error::Cache(_))) {
+            *conn = None;
+            *self.lock_down_until() = Some(Instant::now() + self.backoff);
+        }
+        let replies = result?;
+        for reply in &replies {
+            if let Reply::Error(message) = reply {
+                return Err(Error::Cache(message.clone()));
+            }
+        }
+        Ok(replies)
+    }
+
+    fn check_backoff(&self) -> Result<()> {
+        match *self.lock_down_until() {
+            Some(until) if until > Instant::now() => Err(Error::Cache(format!(
+                "{} is backing off after a failure",
+                self.addr
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock_down_until(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.down_until.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn connect(&self) -> Result<BufStream<TcpStream>> {
+        let mut stream = BufStream::new(TcpStream::connect(&self.addr).await?);
+        if let Some(password) = &self.password {
+            let auth = password.expose_secret().as_bytes();
+            let reply = round_trip(&mut stream, &[vec![&b"AUTH"[..], auth]]).await?;
+            if let Some(Reply::Error(message)) = reply.into_iter().next() {
+                return Err(Error::Cache(message));
+            }
+        }
+        if let Some(database) = self.database {
+            let database = database.to_string();
+            let reply =
+                round_trip(&mut stream, &[vec![&b"SELECT"[..], database.as_bytes()]]).await?;
+            if let Some(Reply::Error(message)) = reply.into_iter().next() {
+                return Err(Error::Cache(message));
+            }
+        }
+        Ok(stream)
+    }
+
+    fn key(&self, key: &str) -> Vec<u8> {
+        format!("{}{key}", self.key_prefix).into_bytes()
+    }
+}
+
+impl RecommendCache for RedisCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            match self.command(&[b"GET", &self.key(key)]).await? {
+                Reply::Bulk(value) => Ok(value),
+                other => Err(unexpected("GET", other)),
+            }
+        })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // Redis rejects a zero expiration
+            let ttl = ttl.as_millis().max(1).to_string();
+            match self
+                .command(&[b"SET", &self.key(key), &value, b"PX", ttl.as_bytes()])
+                .await?
+            {
+                Reply::Simple(status) if status == "OK" => Ok(()),
+                other => Err(unexpected("SET", other)),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match self.command(&[b"DEL", &self.key(key)]).await? {
+                Reply::Integer => Ok(()),
+                other => Err(unexpected("DEL", other)),
+            }
+        })
+    }
+
+    fn get_many<'a>(&'a self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<Option<Vec<u8>>>>> {
+        Box::pin(async move {
+            if keys.is_empty() {
+                return Ok(Vec::new());
+            }
+            let keys: Vec<Vec<u8>> = keys.iter().map(|key| self.key(key)).collect();
+            let mut args: Vec<&[u8]> = vec![b"MGET"];
+            args.extend(keys.iter().map(Vec::as_slice));
+            match self.command(&args).await? {
+                Reply::Array(values) if values.len() == keys.len() => Ok(values),
+                other => Err(unexpected("MGET", other)),
+            }
+        })
+    }
+
+    fn set_many<'a>(
+        &'a self,
+        entries: Vec<(&'a str, Vec<u8>)>,
+        ttl: Duration,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if entries.is_empty() {
+                return Ok(());
+            }
+            let ttl = ttl.as_millis().max(1).to_string();
+            let keys: Vec<Vec<u8>> = entries.iter().map(|(key, _)| self.key(key)).collect();
+            let commands: Vec<Vec<&[u8]>> = keys
+                .iter()
+                .zip(&entries)
+                .map(|(key, (_, value))| vec![&b"SET"[..], key, value, b"PX", ttl.as_bytes()])
+                .collect();
+            for reply in self.pipeline(&commands).await? {
+                match reply {
+                    Reply::Simple(status) if status == "OK" => {}
+                    other => return Err(unexpected("SET", other)),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// The reply types that GET, MGET, SET and DEL can produce
+#[derive(Debug)]
+enum Reply {
+    Simple(String),
+    Error(String),
+    // Only DEL returns one, and its count is of no use
+    Integer,
+    Bulk(Option<Vec<u8>>),
+    // Only MGET returns one, made of bulk strings
+    Array(Vec<Option<Vec<u8>>>),
+}
+
+fn unexpected(command: &str, reply: Reply) -> Error {
+    Error::Cache(format!("unexpected reply to {command}: {reply:?}"))
+}
+
+async fn round_trip(
+    stream: &mut BufStream<TcpStream>,
+    commands: &[Vec<&[u8]>],
+) -> Result<Vec<Reply>> {
+    let mut frame = Vec::new();
+    for args in commands {
+        frame.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for arg in args {
+            frame.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            frame.extend_from_slice(arg);
+            frame.extend_from_slice(b"\r\n");
+        }
+    }
+    stream.write_all(&frame).await?;
+    stream.flush().await?;
+    let mut replies = Vec::with_capacity(commands.len());
+    for _ in commands {
+        replies.push(read_reply(stream).await?);
+    }
+    Ok(replies)
+}
+
+async fn read_reply(stream: &mut BufStream<TcpStream>) -> Result<Reply> {
+    let line = read_line(stream).await?;
+    let (kind, rest) = line.split_at(line.len().min(1));
+    let malformed = || Error::Cache(format!("malformed reply {line:?}"));
+    match kind {
+        "+" => Ok(Reply::Simple(rest.to_string())),
+        "-" => Ok(Reply::Error(rest.to_string())),
+        ":" => {
+            rest.parse::<i64>().map_err(|_| malformed())?;
+            Ok(Reply::Integer)
+        }
+        "$" => Ok(Reply::Bulk(read_bulk(stream, rest).await?)),
+        "*" => {
+            let len: i64 = rest.parse().map_err(|_| malformed())?;
+            let mut values = Vec::new();
+            for _ in 0..len {
+                let line = read_line(stream).await?;
+                match line.strip_prefix('$') {
+                    Some(rest) => values.push(read_bulk(stream, rest).await?),
+                    None => return Err(Error::Cache(format!("malformed reply {line:?}"))),
+                }
+            }
+            Ok(Reply::Array(values))
+        }
+        _ => Err(malformed()),
+    }
+}
+
+async fn read_line(stream: &mut BufStream<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Err(Error::Cache(String::from("connection closed")));
+    }
+    line.truncate(line.trim_end_matches("\r\n").len());
+    Ok(line)
+}
+
+// The value of a bulk string whose `$` line ended with `len`
+async fn read_bulk(stream: &mut BufStream<TcpStream>, len: &str) -> Result<Option<Vec<u8>>> {
+    let len: i64 = len
+        .parse()
+        .map_err(|_| Error::Cache(format!("malformed reply \"${len}\"")))?;
+    if len < 0 {
+        return Ok(None);
+    }
+    if len > MAX_BULK_LEN {
+        return Err(Error::Cache(format!(
+            "malformed reply: bulk string of {len} bytes"
+        )));
+    }
+    let mut value = vec![0; len as usize + 2];
+    stream.read_exact(&mut value).await?;
+    value.truncate(len as usize);
+    Ok(Some(value))
+}
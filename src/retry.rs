@@ -0,0 +1,125 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime};
+
+/// Decides how many times a call is attempted and how long to wait between
+/// attempts. Attempts rotate over the configured hosts, so `max_attempts` may
+/// be lower or higher than the number of hosts.
+pub trait RetryPolicy: Debug + Send + Sync {
+    /// Total number of HTTP attempts for one call, including the first one.
+    fn max_attempts(&self) -> u32;
+
+    /// Delay before the next attempt, given how many attempts already failed
+    /// (starting at 1) and the `Retry-After` hint of the last response, if any.
+    fn backoff(&self, failed_attempts: u32, retry_after: Option<Duration>) -> Duration;
+}
+
+/// Exponential backoff with optional jitter, honoring `Retry-After` up to
+/// `max_delay`.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: bool,
+    pub respect_retry_after: bool,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    /// Retry up to `max_attempts` times without waiting between attempts,
+    /// which is how the client behaved before backoff existed.
+    pub fn no_delay(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            respect_retry_after: false,
+            ..Self::default()
+        }
+    }
+
+    /// Sets what [`RetryPolicy::max_attempts`] returns.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    fn backoff(&self, failed_attempts: u32, retry_after: Option<Duration>) -> Duration {
+        if self.respect_retry_after {
+            if let Some(retry_after) = retry_after {
+                return retry_after.min(self.max_delay);
+            }
+        }
+
+        let exponent = failed_attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_delay.as_secs_f64());
+        let delay = Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay);
+
+        if self.jitter {
+            // "Equal jitter": keep half of the delay, randomize the other half
+            let half = delay / 2;
+            half + half.mul_f64(fastrand::f64())
+        } else {
+            delay
+        }
+    }
+}
+
+/// Parses a `Retry-After` header given either in seconds or as an HTTP date.
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
@@ -0,0 +1,288 @@
+use crate::error::ValidationError;
+use crate::params::FilterList;
+use serde::{Deserialize, Serialize};
+
+/// A Recommend rule, as stored under `/1/indexes/{index}/{model}/recommend/rules`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecommendRule {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consequence: Option<Consequence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    // Maintained by Algolia, never sent back
+    #[serde(default, rename = "_metadata", skip_serializing)]
+    pub metadata: Option<RuleMetadata>,
+}
+
+impl RecommendRule {
+    pub fn new(object_id: impl Into<String>) -> Self {
+        Self {
+            object_id: object_id.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn consequence(mut self, consequence: Consequence) -> Self {
+        self.consequence = Some(consequence);
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Checks what the API would reject anyway, without a round trip.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        if self.object_id.trim().is_empty() {
+            errors.push(ValidationError::new("objectID", "must not be empty"));
+        }
+        if let Some(consequence) = &self.consequence {
+            for item in consequence.promote.iter().flatten() {
+                if item.object_id.trim().is_empty() {
+                    errors.push(ValidationError::new(
+                        "consequence.promote.objectID",
+                        "must not be empty",
+                    ));
+                }
+            }
+            for item in consequence.hide.iter().flatten() {
+                if item.object_id.trim().is_empty() {
+                    errors.push(ValidationError::new(
+                        "consequence.hide.objectID",
+                        "must not be empty",
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleMetadata {
+    #[serde(default, rename = "lastUpdate")]
+    pub last_update: Option<String>,
+}
+
+/// When a rule applies: to items matching `filters`, in `context`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl Condition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.filters = Some(filters.into());
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+}
+
+/// What a rule does to the recommendations.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Consequence {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub promote: Option<Vec<PromotedItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hide: Option<Vec<HiddenItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<ConsequenceParams>,
+}
+
+impl Consequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin `object_id` at `position` (0-based).
+    pub fn promote(mut self, object_id: impl Into<String>, position: u32) -> Self {
+        self.promote
+            .get_or_insert_with(Vec::new)
+            .push(PromotedItem {
+                object_id: object_id.into(),
+                position,
+            });
+        self
+    }
+
+    pub fn hide(mut self, object_id: impl Into<String>) -> Self {
+        self.hide.get_or_insert_with(Vec::new).push(HiddenItem {
+            object_id: object_id.into(),
+        });
+        self
+    }
+
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.params.get_or_insert_with(Default::default).filters = Some(filters.into());
+        self
+    }
+
+    pub fn optional_filters(mut self, filters: impl Into<FilterList>) -> Self {
+        self.params
+            .get_or_insert_with(Default::default)
+            .optional_filters = Some(filters.into());
+        self
+    }
+
+    /// Filter recommendations on the value of `facet` in the reference item.
+    pub fn automatic_facet_filter(mut self, facet: impl Into<String>, negative: bool) -> Self {
+        self.params
+            .get_or_insert_with(Default::default)
+            .automatic_facet_filters
+            .get_or_insert_with(Vec::new)
+            .push(AutomaticFacetFilter {
+                facet: facet.into(),
+                negative: Some(negative),
+            });
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromotedItem {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+    pub position: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HiddenItem {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+}
+
+/// Filters a rule adds to the recommendation query.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsequenceParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optional_filters: Option<FilterList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automatic_facet_filters: Option<Vec<AutomaticFacetFilter>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutomaticFacetFilter {
+    pub facet: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative: Option<bool>,
+}
+
+/// Parameters of a rules search, all optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRulesParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hits_per_page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<String>,
+}
+
+impl SearchRulesParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    pub fn hits_per_page(mut self, hits_per_page: u32) -> Self {
+        self.hits_per_page = Some(hits_per_page);
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    pub fn filters(mut self, filters: impl Into<String>) -> Self {
+        self.filters = Some(filters.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchRulesResponse {
+    #[serde(default)]
+    pub hits: Vec<RecommendRule>,
+    #[serde(default, rename = "nbHits")]
+    pub nb_hits: u32,
+    #[serde(default)]
+    pub page: u32,
+    #[serde(default, rename = "nbPages")]
+    pub nb_pages: u32,
+}
+
+/// Answer to a rules write; the change is live once the task is published.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatedAtResponse {
+    #[serde(rename = "taskID")]
+    pub task_id: i64,
+    #[serde(default, rename = "updatedAt")]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedAtResponse {
+    #[serde(rename = "taskID")]
+    pub task_id: i64,
+    #[serde(default, rename = "deletedAt")]
+    pub deleted_at: Option<String>,
+}
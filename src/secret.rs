@@ -0,0 +1,47 @@
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A credential, such as an Algolia API key or a cache password.
+///
+/// The value is masked in `Debug` output, is neither `Display` nor
+/// `Serialize`, and its memory is zeroed when dropped. Use
+/// [`Secret::expose_secret`] where the raw value is really needed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+/// An Algolia API key.
+pub type ApiKey = Secret;
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
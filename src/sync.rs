@@ -0,0 +1,359 @@
+//! Rules as code: keep the Recommend rules of an index in sync with files.
+//!
+//! Rule files hold either one [`RecommendRule`] or a list of them, as JSON
+//! (`.json`) or, with the `yaml` feature, YAML (`.yaml`, `.yml`). Without
+//! the feature, YAML files are an error rather than being skipped.
+
+use crate::client::RecommendClient;
+use crate::error::{Error, Result, ValidationError};
+use crate::models::Model;
+use crate::rules::{RecommendRule, SearchRulesParams};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+// The largest page the rules search accepts
+const RULES_PAGE_SIZE: u32 = 1000;
+
+/// Loads every rule file found directly in `dir`, in file name order.
+///
+/// Files with other extensions are ignored. An `objectID` defined twice is
+/// an error, since only one of the definitions could win.
+pub fn load_rules_dir(dir: impl AsRef<Path>) -> Result<Vec<RecommendRule>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && RuleFormat::of(&path).is_some() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut rules = Vec::new();
+    let mut seen = HashSet::new();
+    for path in paths {
+        for rule in load_rules_file(&path)? {
+            if !seen.insert(rule.object_id.clone()) {
+                return Err(Error::RuleFile {
+                    path,
+                    message: format!("duplicate objectID {:?}", rule.object_id),
+                });
+            }
+            rules.push(rule);
+        }
+    }
+    Ok(rules)
+}
+
+/// Loads one rule file, see [`load_rules_dir`] for the accepted formats.
+pub fn load_rules_file(path: impl AsRef<Path>) -> Result<Vec<RecommendRule>> {
+    let path = path.as_ref();
+    let Some(format) = RuleFormat::of(path) else {
+        return Err(rule_file_error(path, "unsupported file extension"));
+    };
+    let text = std::fs::read_to_string(path)?;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        Many(Vec<RecommendRule>),
+        One(Box<RecommendRule>),
+    }
+    let parsed: OneOrMany = match format {
+        RuleFormat::Json => {
+            serde_json::from_str(&text).map_err(|e| rule_file_error(path, e.to_string()))?
+        }
+        #[cfg(feature = "yaml")]
+        RuleFormat::Yaml => {
+            serde_norway::from_str(&text).map_err(|e| rule_file_error(path, e.to_string()))?
+        }
+        #[cfg(not(feature = "yaml"))]
+        RuleFormat::Yaml => return Err(rule_file_error(path, "yaml feature not enabled")),
+    };
+    Ok(match parsed {
+        OneOrMany::Many(rules) => rules,
+        OneOrMany::One(rule) => vec![*rule],
+    })
+}
+
+// YAML is recognised whatever the features, so that a YAML directory
+// doesn't load as zero rules and get every live rule deleted
+enum RuleFormat {
+    Json,
+    Yaml,
+}
+
+impl RuleFormat {
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(RuleFormat::Json),
+            "yaml" | "yml" => Some(RuleFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+fn rule_file_error(path: &Path, message: impl Into<String>) -> Error {
+    Error::RuleFile {
+        path: path.to_path_buf(),
+        message: message.into(),
+    }
+}
+
+/// Controls what [`RecommendClient::sync_rules`] does.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Only compute the plan, write nothing.
+    pub dry_run: bool,
+    /// Delete live rules that no file defines. Off by default, since an
+    /// empty or mistyped directory would then delete every rule.
+    pub delete_missing: bool,
+}
+
+impl SyncOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn delete_missing(mut self, delete_missing: bool) -> Self {
+        self.delete_missing = delete_missing;
+        self
+    }
+}
+
+/// One difference between the files and the live rules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleChange {
+    Create(RecommendRule),
+    Update {
+        current: Box<RecommendRule>,
+        desired: Box<RecommendRule>,
+    },
+    Delete(RecommendRule),
+}
+
+impl RuleChange {
+    pub fn object_id(&self) -> &str {
+        match self {
+            RuleChange::Create(rule) | RuleChange::Delete(rule) => &rule.object_id,
+            RuleChange::Update { desired, .. } => &desired.object_id,
+        }
+    }
+}
+
+/// The changes needed to make the live rules of an index and model match the
+/// desired ones, ordered by `objectID`. Displays as a readable plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncPlan {
+    pub index_name: String,
+    pub model: Model,
+    pub changes: Vec<RuleChange>,
+}
+
+impl SyncPlan {
+    /// Computes the plan from already fetched live rules. An `objectID`
+    /// defined twice in `desired` is an error, as in [`load_rules_dir`].
+    pub fn new(
+        index_name: impl Into<String>,
+        model: Model,
+        desired: &[RecommendRule],
+        live: &[RecommendRule],
+        options: &SyncOptions,
+    ) -> Result<Self> {
+        let live: BTreeMap<&str, &RecommendRule> =
+            live.iter().map(|r| (r.object_id.as_str(), r)).collect();
+        let mut by_id = BTreeMap::new();
+        for (index, rule) in desired.iter().enumerate() {
+            if by_id.insert(rule.object_id.as_str(), rule).is_some() {
+                return Err(Error::Validation {
+                    errors: vec![ValidationError {
+                        index: Some(index),
+                        ..ValidationError::new(
+                            "objectID",
+                            format!("duplicate objectID {:?}", rule.object_id),
+                        )
+                    }],
+                });
+            }
+        }
+        let desired = by_id;
+
+        let mut changes = BTreeMap::new();
+        for (id, rule) in &desired {
+            match live.get(id) {
+                None => {
+                    changes.insert(*id, RuleChange::Create((*rule).clone()));
+                }
+                Some(current) if normalized(current) != normalized(rule) => {
+                    changes.insert(
+                        *id,
+                        RuleChange::Update {
+                            current: Box::new((*current).clone()),
+                            desired: Box::new((*rule).clone()),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+        if options.delete_missing {
+            for (id, rule) in &live {
+                if !desired.contains_key(id) {
+                    changes.insert(*id, RuleChange::Delete((*rule).clone()));
+                }
+            }
+        }
+
+        Ok(Self {
+            index_name: index_name.into(),
+            model,
+            changes: changes.into_values().collect(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Recommend rules of {} ({}):",
+            self.index_name, self.model
+        )?;
+        if self.changes.is_empty() {
+            return writeln!(f, "  no changes");
+        }
+        for change in &self.changes {
+            let action = match change {
+                RuleChange::Create(_) => "+ create",
+                RuleChange::Update { .. } => "~ update",
+                RuleChange::Delete(_) => "- delete",
+            };
+            writeln!(f, "  {action} {}", change.object_id())?;
+        }
+        let count = |pred: fn(&RuleChange) -> bool| self.changes.iter().filter(|c| pred(c)).count();
+        writeln!(
+            f,
+            "{} to create, {} to update, {} to delete",
+            count(|c| matches!(c, RuleChange::Create(_))),
+            count(|c| matches!(c, RuleChange::Update { .. })),
+            count(|c| matches!(c, RuleChange::Delete(_))),
+        )
+    }
+}
+
+// Ignores what Algolia fills in by itself, so that untouched rules don't show
+// up as updates
+fn normalized(rule: &RecommendRule) -> RecommendRule {
+    RecommendRule {
+        metadata: None,
+        enabled: Some(rule.enabled.unwrap_or(true)),
+        ..rule.clone()
+    }
+}
+
+/// What [`RecommendClient::sync_rules`] did.
+#[derive(Debug, Clone)]
+pub struct SyncReport {
+    pub plan: SyncPlan,
+    /// Tasks of the writes, to pass to [`RecommendClient::wait_for_task`].
+    /// Empty for a dry run or an empty plan.
+    pub task_ids: Vec<i64>,
+}
+
+impl RecommendClient {
+    /// Fetches every live rule of `index_name` for `model`, page by page.
+    pub async fn list_recommend_rules(
+        &self,
+        index_name: &str,
+        model: &Model,
+    ) -> Result<Vec<RecommendRule>> {
+        let mut rules = Vec::new();
+        let mut page = 0;
+        loop {
+            let params = SearchRulesParams::new()
+                .page(page)
+                .hits_per_page(RULES_PAGE_SIZE);
+            let resp = self
+                .search_recommend_rules(index_name, model, &params)
+                .await?;
+            rules.extend(resp.hits);
+            page += 1;
+            if page >= resp.nb_pages {
+                return Ok(rules);
+            }
+        }
+    }
+
+    /// Diffs `desired` against the live rules without changing anything.
+    pub async fn plan_rules_sync(
+        &self,
+        index_name: &str,
+        model: &Model,
+        desired: &[RecommendRule],
+        options: &SyncOptions,
+    ) -> Result<SyncPlan> {
+        let live = self.list_recommend_rules(index_name, model).await?;
+        SyncPlan::new(index_name, model.clone(), desired, &live, options)
+    }
+
+    /// Writes `plan`: creates and updates in one batch, then the deletions.
+    /// Returns the task of every write.
+    pub async fn apply_rules_sync(&self, plan: &SyncPlan) -> Result<Vec<i64>> {
+        let mut task_ids = Vec::new();
+
+        let upserts: Vec<RecommendRule> = plan
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                RuleChange::Create(rule) => Some(rule.clone()),
+                RuleChange::Update { desired, .. } => Some((**desired).clone()),
+                RuleChange::Delete(_) => None,
+            })
+            .collect();
+        if !upserts.is_empty() {
+            let resp = self
+                .save_recommend_rules(&plan.index_name, &plan.model, &upserts)
+                .await?;
+            task_ids.push(resp.task_id);
+        }
+
+        for change in &plan.changes {
+            if let RuleChange::Delete(rule) = change {
+                let resp = self
+                    .delete_recommend_rule(&plan.index_name, &plan.model, &rule.object_id)
+                    .await?;
+                task_ids.push(resp.task_id);
+            }
+        }
+        Ok(task_ids)
+    }
+
+    /// Makes the live rules match `desired`, or only plans it on a dry run.
+    pub async fn sync_rules(
+        &self,
+        index_name: &str,
+        model: &Model,
+        desired: &[RecommendRule],
+        options: &SyncOptions,
+    ) -> Result<SyncReport> {
+        let plan = self
+            .plan_rules_sync(index_name, model, desired, options)
+            .await?;
+        let task_ids = if options.dry_run {
+            Vec::new()
+        } else {
+            self.apply_rules_sync(&plan).await?
+        };
+        Ok(SyncReport { plan, task_ids })
+    }
+}
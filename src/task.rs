@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Publication state of an asynchronous write, such as a rules batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    Published,
+    NotPublished,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecommendTaskResponse {
+    pub status: TaskStatus,
+}
+
+// Shorter intervals, even zero, would hammer the API
+const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How [`RecommendClient::wait_for_task`](crate::RecommendClient::wait_for_task)
+/// polls: the interval grows by `multiplier` up to `max_interval`, and polling
+/// gives up once `max_wait` has elapsed. Intervals under 10ms are raised to
+/// 10ms.
+#[derive(Debug, Clone)]
+pub struct WaitForTaskOptions {
+    pub interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    pub max_wait: Duration,
+}
+
+impl Default for WaitForTaskOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            max_interval: Duration::from_secs(5),
+            multiplier: 1.5,
+            max_wait: Duration::from_secs(120),
+        }
+    }
+}
+
+impl WaitForTaskOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn max_interval(mut self, interval: Duration) -> Self {
+        self.max_interval = interval;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    pub(crate) fn first_interval(&self) -> Duration {
+        self.interval.max(MIN_INTERVAL)
+    }
+
+    pub(crate) fn next_interval(&self, interval: Duration) -> Duration {
+        Duration::try_from_secs_f64(interval.as_secs_f64() * self.multiplier)
+            .unwrap_or(self.max_interval)
+            .min(self.max_interval)
+            .max(MIN_INTERVAL)
+    }
+}
@@ -0,0 +1,213 @@
+use algolia_recommend_rs::batch::RecommendBatch;
+use algolia_recommend_rs::{CacheConfig, Error, RecommendClient, RecommendRequest};
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Product {
+    price: f64,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Category {
+    name: String,
+}
+
+#[tokio::test]
+async fn test_batch_splits_results_into_typed_hits() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .json_body(json!({
+                "requests": [
+                    { "indexName": "products", "model": "bought-together", "objectID": "obj-1", "threshold": 0 },
+                    { "indexName": "categories", "model": "trending-items", "threshold": 0 }
+                ]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "results": [
+                    { "hits": [{ "objectID": "p1", "_score": 90.0, "price": 12.5 }] },
+                    { "hits": [{ "objectID": "c1", "name": "Shoes" }, { "objectID": "c2", "name": "Bags" }] }
+                ]
+            }));
+    });
+
+    let mut batch = RecommendBatch::new();
+    let products = batch.add::<Product>(RecommendRequest::bought_together("products", "obj-1"));
+    let categories = batch.add::<Category>(RecommendRequest::trending_items("categories"));
+    assert_eq!(batch.len(), 2);
+    assert_eq!(categories.index(), 1);
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let response = client
+        .get_recommendations_batch(batch)
+        .await
+        .expect("request ok");
+    mock.assert();
+
+    let products = response.get(&products).expect("products parse");
+    assert_eq!(products.hits[0].object_id, "p1");
+    assert_eq!(products.hits[0].payload, Product { price: 12.5 });
+
+    let categories = response.get(&categories).expect("categories parse");
+    let names: Vec<_> = categories
+        .hits
+        .iter()
+        .map(|h| h.payload.name.as_str())
+        .collect();
+    assert_eq!(names, ["Shoes", "Bags"]);
+}
+
+#[tokio::test]
+async fn test_batch_reports_errors_per_request() {
+    let server = MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "results": [
+                    { "hits": [{ "objectID": "c1", "name": "Shoes" }] },
+                    { "hits": [{ "objectID": "p1", "price": "not a number" }] }
+                ]
+            }));
+    });
+
+    let mut batch = RecommendBatch::new();
+    let categories = batch.add::<Category>(RecommendRequest::trending_items("categories"));
+    let products = batch.add::<Product>(RecommendRequest::related_products("products", "obj-1"));
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let response = client
+        .get_recommendations_batch(batch)
+        .await
+        .expect("request ok");
+
+    assert_eq!(response.get(&categories).unwrap().hits.len(), 1);
+    assert!(matches!(response.get(&products), Err(Error::Serde(_))));
+}
+
+#[tokio::test]
+async fn test_batch_fails_when_results_are_missing() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": [{ "hits": [] }] }));
+    });
+
+    let mut batch = RecommendBatch::new();
+    batch.add::<Category>(RecommendRequest::trending_items("categories"));
+    batch.add::<Product>(RecommendRequest::related_products("products", "obj-1"));
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations_batch(batch)
+        .await
+        .expect_err("one result for two requests");
+    assert!(matches!(err, Error::MissingResult { index: 1 }), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_batch_goes_through_the_cache() {
+    let server = MockServer::start();
+    let first = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .json_body(json!({
+                "requests": [
+                    { "indexName": "products", "model": "bought-together", "objectID": "obj-1", "threshold": 0 },
+                    { "indexName": "categories", "model": "trending-items", "threshold": 0 }
+                ]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "results": [
+                    { "hits": [{ "objectID": "p1", "price": 12.5 }] },
+                    { "hits": [{ "objectID": "c1", "name": "Shoes" }] }
+                ]
+            }));
+    });
+    let second = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .json_body(json!({
+                "requests": [
+                    { "indexName": "products", "model": "bought-together", "objectID": "obj-2", "threshold": 0 }
+                ]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": [{ "hits": [{ "objectID": "p2", "price": 3.0 }] }] }));
+    });
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache(CacheConfig::new())
+        .build()
+        .expect("client builds");
+    let mut batch = RecommendBatch::new();
+    batch.add::<Product>(RecommendRequest::bought_together("products", "obj-1"));
+    batch.add::<Category>(RecommendRequest::trending_items("categories"));
+    client
+        .get_recommendations_batch(batch)
+        .await
+        .expect("request ok");
+
+    // Only the request that isn't cached is sent
+    let mut batch = RecommendBatch::new();
+    let categories = batch.add::<Category>(RecommendRequest::trending_items("categories"));
+    let products = batch.add::<Product>(RecommendRequest::bought_together("products", "obj-2"));
+    let response = client
+        .get_recommendations_batch(batch)
+        .await
+        .expect("request ok");
+
+    first.assert();
+    second.assert();
+    assert_eq!(
+        response.get(&categories).unwrap().hits[0].payload.name,
+        "Shoes"
+    );
+    assert_eq!(response.get(&products).unwrap().hits[0].payload.price, 3.0);
+    assert_eq!(client.cache_stats().unwrap().hits, 1);
+}
+
+#[tokio::test]
+async fn test_batch_handles_only_read_their_own_batch() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": [{ "hits": [{ "objectID": "p1", "price": 3.0 }] }] }));
+    });
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let mut first = RecommendBatch::new();
+    let product = first.add::<Product>(RecommendRequest::bought_together("products", "obj-1"));
+    let mut second = RecommendBatch::new();
+    let other = second.add::<Product>(RecommendRequest::bought_together("products", "obj-2"));
+
+    let response = client
+        .get_recommendations_batch(first)
+        .await
+        .expect("request ok");
+    assert_eq!(response.get(&product).unwrap().hits[0].payload.price, 3.0);
+    let err = response
+        .get(&other)
+        .expect_err("handle of the second batch");
+    assert!(
+        matches!(err, Error::ForeignBatchHandle { index: 0 }),
+        "{err:?}"
+    );
+}
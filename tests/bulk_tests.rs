@@ -0,0 +1,212 @@
+use algolia_recommend_rs::{
+    BulkOptions, CacheConfig, Error, ExponentialBackoff, RecommendClient, RecommendRequest,
+};
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+struct Product {}
+
+fn requests(count: usize) -> Vec<RecommendRequest> {
+    (0..count)
+        .map(|i| RecommendRequest::related_products("products", format!("o{i}")))
+        .collect()
+}
+
+// Answers the chunk whose first request is `first` with one hit per request
+fn mock_chunk<'a>(
+    server: &'a MockServer,
+    first: usize,
+    len: usize,
+    delay: u64,
+) -> httpmock::Mock<'a> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .body_includes(format!("\"objectID\":\"o{first}\""));
+        let results: Vec<_> = (first..first + len)
+            .map(|i| json!({ "hits": [{ "objectID": format!("rec-o{i}") }] }))
+            .collect();
+        then.status(200)
+            .delay(Duration::from_millis(delay))
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": results }));
+    })
+}
+
+#[tokio::test]
+async fn test_bulk_chunks_requests_and_preserves_order() {
+    let server = MockServer::start();
+    // The first chunk answers last, order must still follow the input
+    let chunks = [
+        mock_chunk(&server, 0, 2, 150),
+        mock_chunk(&server, 2, 2, 0),
+        mock_chunk(&server, 4, 1, 0),
+    ];
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let bulk = client
+        .get_recommendations_bulk::<Product>(
+            requests(5),
+            &BulkOptions::new().chunk_size(2).concurrency(3),
+        )
+        .await
+        .expect("bulk ok");
+
+    for chunk in &chunks {
+        chunk.assert();
+    }
+    assert!(bulk.is_complete());
+    let response = bulk.into_response().expect("no failures");
+    let ids: Vec<_> = response
+        .results
+        .iter()
+        .map(|r| r.hits[0].object_id.as_str())
+        .collect();
+    assert_eq!(ids, ["rec-o0", "rec-o1", "rec-o2", "rec-o3", "rec-o4"]);
+}
+
+#[tokio::test]
+async fn test_bulk_result_count_errors_point_into_their_chunk() {
+    let server = MockServer::start();
+    mock_chunk(&server, 0, 2, 0);
+    // One result short, then one too many
+    mock_chunk(&server, 2, 1, 0);
+    mock_chunk(&server, 4, 3, 0);
+
+    // The same positions whichever path the chunks take
+    let builders = [
+        RecommendClient::builder("APPID", "KEY"),
+        RecommendClient::builder("APPID", "KEY").cache(CacheConfig::new()),
+        RecommendClient::builder("APPID", "KEY").coalesce_requests(true),
+    ];
+    for builder in builders {
+        let client = builder
+            .base_url(server.base_url())
+            .build()
+            .expect("client builds");
+        let bulk = client
+            .get_recommendations_bulk::<Product>(requests(6), &BulkOptions::new().chunk_size(2))
+            .await
+            .expect("bulk ok");
+
+        let failures: Vec<_> = bulk
+            .failures
+            .iter()
+            .map(|f| (f.requests.clone(), f.error.to_string()))
+            .collect();
+        assert_eq!(
+            failures,
+            [
+                (2..4, Error::MissingResult { index: 3 }.to_string()),
+                (
+                    4..6,
+                    Error::ResultCountMismatch {
+                        expected: 2,
+                        got: 3
+                    }
+                    .to_string()
+                ),
+            ]
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_bulk_reports_failed_chunks() {
+    let server = MockServer::start();
+    mock_chunk(&server, 0, 2, 0);
+    let failing = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .body_includes("\"objectID\":\"o2\"");
+        then.status(500).body(r#"{"message":"boom"}"#);
+    });
+    // One result short
+    mock_chunk(&server, 4, 1, 0);
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .build()
+        .expect("client builds");
+    let bulk = client
+        .get_recommendations_bulk::<Product>(
+            requests(6),
+            &BulkOptions::new().chunk_size(2).concurrency(2),
+        )
+        .await
+        .expect("bulk ok");
+
+    failing.assert();
+    assert_eq!(bulk.results.len(), 6);
+    let present: Vec<_> = bulk.results.iter().map(Option::is_some).collect();
+    assert_eq!(present, [true, true, false, false, false, false]);
+
+    assert_eq!(bulk.failures.len(), 2);
+    assert_eq!(bulk.failures[0].chunk, 1);
+    assert_eq!(bulk.failures[0].requests, 2..4);
+    assert!(bulk.failures[0].error.to_string().contains("boom"));
+    assert_eq!(bulk.failures[1].requests, 4..6);
+    assert!(matches!(
+        bulk.failures[1].error,
+        Error::MissingResult { index: 5 }
+    ));
+
+    let err = bulk.into_response().expect_err("partial failure");
+    assert!(err
+        .to_string()
+        .starts_with("2 chunk(s) of the bulk call failed, first on requests 2..4"));
+    // The 500 may go away
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_bulk_failure_is_retryable_only_if_a_chunk_is() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(403).body(r#"{"message":"Invalid API key"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations_bulk::<Product>(requests(4), &BulkOptions::new().chunk_size(2))
+        .await
+        .expect("bulk ok")
+        .into_response()
+        .expect_err("every chunk failed");
+
+    let Error::BulkFailed { failures } = &err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(failures.len(), 2);
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_bulk_validates_whole_input_before_sending() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.any_request();
+        then.status(200).body(r#"{"results":[]}"#);
+    });
+
+    let mut input = requests(5);
+    input[3].index_name = String::new();
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations_bulk::<Product>(input, &BulkOptions::new().chunk_size(2))
+        .await
+        .expect_err("invalid input");
+
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors[0].index, Some(3));
+    assert_eq!(mock.calls(), 0);
+}
@@ -0,0 +1,383 @@
+use algolia_recommend_rs::{
+    CacheConfig, Error, ExponentialBackoff, RecommendCache, RecommendClient, RequestOptions,
+};
+use futures_util::future::BoxFuture;
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+use common::{body, mock_batch, request, PATH};
+
+#[derive(Debug, Deserialize)]
+struct Product {}
+
+fn cached_client(server: &MockServer, config: CacheConfig) -> RecommendClient {
+    RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache(config)
+        .build()
+        .expect("client builds")
+}
+
+fn hit_ids(response: &algolia_recommend_rs::RecommendResponse<Product>) -> Vec<&str> {
+    response
+        .results
+        .iter()
+        .map(|r| r.hits[0].object_id.as_str())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_cache_sends_only_misses_of_a_batch() {
+    let server = MockServer::start();
+    let first = mock_batch(&server, &["a", "b"]);
+    let second = mock_batch(&server, &["c"]);
+
+    let client = cached_client(&server, CacheConfig::new());
+
+    let response = client
+        .get_recommendations::<Product>(vec![request("a"), request("b")])
+        .await
+        .expect("request ok");
+    assert_eq!(hit_ids(&response), ["rec-a", "rec-b"]);
+
+    let response = client
+        .get_recommendations::<Product>(vec![request("b"), request("c"), request("a")])
+        .await
+        .expect("request ok");
+    assert_eq!(hit_ids(&response), ["rec-b", "rec-c", "rec-a"]);
+
+    first.assert();
+    second.assert();
+    let stats = client.cache_stats().expect("cache enabled");
+    assert_eq!((stats.hits, stats.misses, stats.entries), (2, 3, 3));
+
+    // Everything is cached now
+    client
+        .get_recommendations::<Product>(vec![request("c"), request("a")])
+        .await
+        .expect("request ok");
+    assert_eq!(first.calls() + second.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_cache_entries_expire_after_ttl() {
+    let server = MockServer::start();
+    let mock = mock_batch(&server, &["a"]);
+
+    let client = cached_client(&server, CacheConfig::new().ttl(Duration::from_millis(50)));
+    for _ in 0..2 {
+        client
+            .get_recommendations::<Product>(vec![request("a")])
+            .await
+            .expect("request ok");
+    }
+    assert_eq!(mock.calls(), 1);
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    assert_eq!(mock.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_cache_evicts_least_recently_used() {
+    let server = MockServer::start();
+    let a = mock_batch(&server, &["a"]);
+    let b = mock_batch(&server, &["b"]);
+    let c = mock_batch(&server, &["c"]);
+
+    let client = cached_client(&server, CacheConfig::new().max_entries(2));
+    for id in ["a", "b", "a", "c", "a", "b"] {
+        client
+            .get_recommendations::<Product>(vec![request(id)])
+            .await
+            .expect("request ok");
+    }
+
+    // "b" was the least recently used when "c" came in
+    assert_eq!((a.calls(), b.calls(), c.calls()), (1, 2, 1));
+    let stats = client.cache_stats().unwrap();
+    assert_eq!(stats.entries, 2);
+    assert_eq!(stats.evictions, 2);
+}
+
+#[tokio::test]
+async fn test_cache_respects_max_bytes() {
+    let server = MockServer::start();
+    let mock = mock_batch(&server, &["a"]);
+
+    let client = cached_client(&server, CacheConfig::new().max_bytes(16));
+    for _ in 0..2 {
+        client
+            .get_recommendations::<Product>(vec![request("a")])
+            .await
+            .expect("request ok");
+    }
+
+    assert_eq!(mock.calls(), 2);
+    assert_eq!(client.cache_stats().unwrap().bytes, 0);
+}
+
+#[tokio::test]
+async fn test_cache_bypass_refreshes_entries() {
+    let server = MockServer::start();
+    let mock = mock_batch(&server, &["a"]);
+
+    let client = cached_client(&server, CacheConfig::new());
+    let bypass = RequestOptions::new().bypass_cache(true);
+    client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    client
+        .get_recommendations_with_options::<Product>(vec![request("a")], &bypass)
+        .await
+        .expect("request ok");
+    assert_eq!(mock.calls(), 2);
+
+    client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    assert_eq!(mock.calls(), 2);
+    let stats = client.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses), (1, 2));
+
+    client.clear_cache();
+    assert_eq!(client.cache_stats().unwrap().entries, 0);
+}
+
+#[tokio::test]
+async fn test_cache_keeps_users_apart() {
+    let server = MockServer::start();
+    let mock = mock_batch(&server, &["a"]);
+
+    let client = cached_client(&server, CacheConfig::new());
+    for token in ["alice", "bob", "alice"] {
+        client
+            .get_recommendations_with_options::<Product>(
+                vec![request("a")],
+                &RequestOptions::new().user_token(token),
+            )
+            .await
+            .expect("request ok");
+    }
+
+    // Bob's call was not answered with Alice's results
+    assert_eq!(mock.calls(), 2);
+    let stats = client.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+}
+
+#[tokio::test]
+async fn test_cache_serves_stale_while_revalidating() {
+    let server = MockServer::start();
+    let mut first = mock_batch(&server, &["a"]);
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache(CacheConfig::new().ttl(Duration::from_millis(50)))
+        .stale_while_revalidate(Duration::from_secs(60))
+        .build()
+        .expect("client builds");
+    let response = client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    assert!(!response.results[0].stale);
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    first.delete();
+    let updated = server.mock(|when, then| {
+        when.method(POST).path(PATH).json_body(body(&["a"]));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": [{ "hits": [{ "objectID": "rec-a2" }] }] }));
+    });
+
+    // Answered from the cache, refreshed behind the scenes
+    let response = client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    assert!(response.results[0].stale);
+    assert_eq!(hit_ids(&response), ["rec-a"]);
+
+    for _ in 0..100 {
+        if updated.calls() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let response = client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+    assert!(!response.results[0].stale);
+    assert_eq!(hit_ids(&response), ["rec-a2"]);
+    assert_eq!(updated.calls(), 1);
+    assert_eq!(client.cache_stats().unwrap().stale, 1);
+}
+
+#[tokio::test]
+async fn test_cache_serves_stale_on_error() {
+    let server = MockServer::start();
+    let mut ok = mock_batch(&server, &["a"]);
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache(CacheConfig::new().ttl(Duration::from_millis(50)))
+        .stale_if_error(Duration::from_secs(60))
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .build()
+        .expect("client builds");
+    client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("request ok");
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    ok.delete();
+    let down = server.mock(|when, then| {
+        when.method(POST).path(PATH);
+        then.status(503).body("unavailable");
+    });
+
+    let response = client
+        .get_recommendations::<Product>(vec![request("a")])
+        .await
+        .expect("served stale");
+    assert!(response.results[0].stale);
+    assert_eq!(hit_ids(&response), ["rec-a"]);
+    assert_eq!(down.calls(), 1);
+
+    // Nothing to fall back on for "b"
+    client
+        .get_recommendations::<Product>(vec![request("a"), request("b")])
+        .await
+        .expect_err("b has no cached result");
+    let stats = client.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.stale), (0, 1));
+}
+
+// Holds a result for every request, unreadable for the ones of "a"
+#[derive(Debug)]
+struct CorruptCache;
+
+impl RecommendCache for CorruptCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, Error>> {
+        let value = if key.contains(r#""objectID":"a""#) {
+            format!("{}\n{{\"hits\":[", u64::MAX)
+        } else {
+            format!(
+                "{}\n{{\"hits\":[{{\"objectID\":\"rec-cached\"}}]}}",
+                u64::MAX
+            )
+        };
+        Box::pin(async move { Ok(Some(value.into_bytes())) })
+    }
+
+    fn set<'a>(
+        &'a self,
+        _key: &'a str,
+        _value: Vec<u8>,
+        _ttl: Duration,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, _key: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn test_cache_sends_requests_whose_entry_is_corrupt() {
+    let server = MockServer::start();
+    let mock = mock_batch(&server, &["a"]);
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache_backend(CorruptCache, Duration::from_secs(60))
+        .build()
+        .expect("client builds");
+
+    let response = client
+        .get_recommendations::<Product>(vec![request("a"), request("b")])
+        .await
+        .expect("corrupt entry is not an error");
+
+    mock.assert();
+    assert_eq!(hit_ids(&response), ["rec-a", "rec-cached"]);
+    let stats = client.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.errors), (1, 1, 1));
+}
+
+// Remembers every key it was asked for
+#[derive(Debug, Clone, Default)]
+struct RecordingCache {
+    keys: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecommendCache for RecordingCache {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, Error>> {
+        self.keys.lock().unwrap().push(key.to_string());
+        Box::pin(async { Ok(None) })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        _value: Vec<u8>,
+        _ttl: Duration,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        self.keys.lock().unwrap().push(key.to_string());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, _key: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn test_cache_keys_hide_header_values() {
+    let server = MockServer::start();
+    mock_batch(&server, &["a"]);
+
+    let backend = RecordingCache::default();
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache_backend(backend.clone(), Duration::from_secs(60))
+        .build()
+        .expect("client builds");
+    for name in ["X-Forwarded-For", "x-forwarded-for"] {
+        client
+            .get_recommendations_with_options::<Product>(
+                vec![request("a")],
+                &RequestOptions::new()
+                    .header("x-algolia-api-key", "SECURED-USER-KEY-abc123")
+                    .header(name, "10.0.0.1"),
+            )
+            .await
+            .expect("request ok");
+    }
+
+    let keys = backend.keys.lock().unwrap();
+    assert_eq!(keys.len(), 4);
+    assert!(keys.iter().all(|key| key == &keys[0]));
+    assert!(!keys[0].contains("SECURED-USER-KEY"));
+    assert!(!keys[0].contains("10.0.0.1"));
+    // Headers and query parameters as a SHA-256
+    let (_, metadata) = keys[0].rsplit_once('|').expect("metadata digest");
+    assert_eq!(metadata.len(), 64);
+    assert!(metadata.bytes().all(|b| b.is_ascii_hexdigit()));
+}
@@ -0,0 +1,161 @@
+use algolia_recommend_rs::{
+    Error, ExponentialBackoff, RecommendClient, RecommendRequest, RecommendResponse, RequestOptions,
+};
+use futures_util::future::join_all;
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use std::time::Duration;
+
+mod common;
+
+use common::{body, mock_slow_batch, request, results, PATH};
+
+#[derive(Debug, Deserialize)]
+struct Product {}
+
+fn coalescing_client(server: &MockServer) -> RecommendClient {
+    RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .coalesce_requests(true)
+        .build()
+        .expect("client builds")
+}
+
+fn hot() -> Vec<RecommendRequest> {
+    vec![request("hot-1")]
+}
+
+#[tokio::test]
+async fn test_concurrent_identical_requests_share_one_call() {
+    let server = MockServer::start_async().await;
+    let mock = mock_slow_batch(&server, &["hot-1"], Duration::from_millis(100));
+
+    let client = coalescing_client(&server);
+    let responses: Vec<RecommendResponse<Product>> =
+        join_all((0..10).map(|_| client.get_recommendations::<Product>(hot())))
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .expect("every call ok");
+
+    assert_eq!(mock.calls(), 1);
+    for response in &responses {
+        assert_eq!(response.results[0].hits[0].object_id, "rec-hot-1");
+    }
+
+    // Nothing in flight any more, the next call is sent
+    client
+        .get_recommendations::<Product>(hot())
+        .await
+        .expect("request ok");
+    assert_eq!(mock.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_failure_of_a_shared_call_reaches_every_caller() {
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path(PATH);
+        then.status(503)
+            .delay(Duration::from_millis(100))
+            .body("unavailable");
+    });
+
+    let client = coalescing_client(&server);
+    let outcomes = join_all((0..3).map(|_| client.get_recommendations::<Product>(hot()))).await;
+
+    assert_eq!(mock.calls(), 1);
+    for outcome in outcomes {
+        let err = outcome.expect_err("upstream is down");
+        let Error::Coalesced(inner) = &err else {
+            panic!("expected a shared error, got {err:?}");
+        };
+        assert!(matches!(**inner, Error::AllHostsFailed { .. }), "{inner:?}");
+        assert!(err.is_retryable());
+    }
+}
+
+#[tokio::test]
+async fn test_requests_for_other_users_are_not_coalesced() {
+    let server = MockServer::start_async().await;
+    let mock = mock_slow_batch(&server, &["hot-1"], Duration::from_millis(50));
+
+    let client = coalescing_client(&server);
+    let (alice, bob) = (
+        RequestOptions::new().user_token("alice"),
+        RequestOptions::new().user_token("bob"),
+    );
+    let (a, b) = tokio::join!(
+        client.get_recommendations_with_options::<Product>(hot(), &alice),
+        client.get_recommendations_with_options::<Product>(hot(), &bob),
+    );
+    a.expect("request ok");
+    b.expect("request ok");
+
+    assert_eq!(mock.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_extra_results_are_an_error() {
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path(PATH).json_body(body(&["hot-1"]));
+        then.status(200)
+            .header("content-type", "application/json")
+            .delay(Duration::from_millis(50))
+            .json_body(results(&["hot-1", "hot-1"]));
+    });
+
+    let client = coalescing_client(&server);
+    let outcomes = join_all((0..2).map(|_| client.get_recommendations::<Product>(hot()))).await;
+
+    // The follower sends its own request once the leader gives up
+    assert_eq!(mock.calls(), 2);
+    for outcome in outcomes {
+        let err = outcome.expect_err("two results for one request");
+        assert!(
+            matches!(
+                err,
+                Error::ResultCountMismatch {
+                    expected: 1,
+                    got: 2
+                }
+            ),
+            "{err:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_calls_with_other_limits_are_not_coalesced() {
+    let server = MockServer::start_async().await;
+    let mock = mock_slow_batch(&server, &["hot-1"], Duration::from_millis(300));
+
+    let client = coalescing_client(&server);
+    let hurried = RequestOptions::new().timeout(Duration::from_millis(50));
+    let started = std::time::Instant::now();
+    let (patient, in_a_hurry) = tokio::join!(client.get_recommendations::<Product>(hot()), async {
+        let outcome = client
+            .get_recommendations_with_options::<Product>(hot(), &hurried)
+            .await;
+        (outcome, started.elapsed())
+    },);
+
+    patient.expect("request ok");
+    let (outcome, took) = in_a_hurry;
+    outcome.expect_err("its own call times out");
+    assert!(took < Duration::from_millis(250), "{took:?}");
+    assert_eq!(mock.calls(), 2);
+
+    // Nor does a call retried otherwise wait for this client's
+    let retrying = client.with_retry_policy(ExponentialBackoff::no_delay(3));
+    let (a, b) = tokio::join!(
+        client.get_recommendations::<Product>(hot()),
+        retrying.get_recommendations::<Product>(hot()),
+    );
+    a.expect("request ok");
+    b.expect("request ok");
+    assert_eq!(mock.calls(), 4);
+}
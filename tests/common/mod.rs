@@ -0,0 +1,58 @@
+//! Fixtures shared by the integration tests.
+
+// Each test crate uses its own subset
+#![allow(dead_code)]
+
+use algolia_recommend_rs::RecommendRequest;
+use httpmock::prelude::*;
+use serde_json::json;
+use std::time::Duration;
+
+pub const PATH: &str = "/1/indexes/*/recommendations";
+
+pub fn request(object_id: &str) -> RecommendRequest {
+    RecommendRequest::related_products("products", object_id)
+}
+
+pub fn body(object_ids: &[&str]) -> serde_json::Value {
+    json!({
+        "requests": object_ids
+            .iter()
+            .map(|id| json!({
+                "indexName": "products",
+                "model": "related-products",
+                "objectID": id,
+                "threshold": 0
+            }))
+            .collect::<Vec<_>>()
+    })
+}
+
+pub fn results(object_ids: &[&str]) -> serde_json::Value {
+    json!({
+        "results": object_ids
+            .iter()
+            .map(|id| json!({ "hits": [{ "objectID": format!("rec-{id}") }] }))
+            .collect::<Vec<_>>()
+    })
+}
+
+// Answers exactly a batch of `object_ids`, in that order
+pub fn mock_batch<'a>(server: &'a MockServer, object_ids: &[&str]) -> httpmock::Mock<'a> {
+    mock_slow_batch(server, object_ids, Duration::ZERO)
+}
+
+// Like `mock_batch`, answering after `delay` so that calls overlap
+pub fn mock_slow_batch<'a>(
+    server: &'a MockServer,
+    object_ids: &[&str],
+    delay: Duration,
+) -> httpmock::Mock<'a> {
+    server.mock(|when, then| {
+        when.method(POST).path(PATH).json_body(body(object_ids));
+        then.status(200)
+            .header("content-type", "application/json")
+            .delay(delay)
+            .json_body(results(object_ids));
+    })
+}
@@ -0,0 +1,201 @@
+use algolia_recommend_rs::filters::{FacetFilter, FacetFilters, Filter, NumericOp};
+use algolia_recommend_rs::{RecommendRequest, TrendingFacetsRequest};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+#[test]
+fn test_filter_renders_algolia_syntax_with_quoting() {
+    let filter = Filter::facet("brand", r#"Acme "Pro" Tools"#)
+        & Filter::range("price", 10, 49.5)
+        & Filter::numeric("stock", NumericOp::Gte, 1)
+        & Filter::facet("category name", "AND");
+
+    assert_eq!(
+        filter.to_string(),
+        r#"brand:"Acme \"Pro\" Tools" AND price:10 TO 49.5 AND stock >= 1 AND "category name":"AND""#
+    );
+}
+
+#[test]
+fn test_filter_moves_not_onto_single_filters() {
+    let filter = !(Filter::facet("a", "1") | Filter::facet("b", "2"));
+    assert_eq!(filter.to_string(), "NOT (a:1 OR b:2)");
+    assert_eq!(filter.try_to_string().unwrap(), "NOT a:1 AND NOT b:2");
+
+    let filter = Filter::facet("c", "3") & !(Filter::facet("a", "1") & !Filter::facet("b", "2"));
+    assert_eq!(filter.try_to_string().unwrap(), "c:3 AND (NOT a:1 OR b:2)");
+
+    let filter = Filter::any([
+        Filter::facet("brand", "Acme").score(3),
+        Filter::facet("brand", "Globex"),
+    ]);
+    assert_eq!(
+        filter.try_to_string().unwrap(),
+        "brand:Acme<score=3> OR brand:Globex"
+    );
+}
+
+#[test]
+fn test_filter_rejects_shapes_the_api_does_not_accept() {
+    let filter = Filter::facet("brand", "Acme")
+        & (Filter::numeric("price", NumericOp::Lt, 50) | Filter::tag("sale"));
+    let err = filter.try_to_string().unwrap_err();
+    assert_eq!(
+        err.message,
+        "numeric and tag filters can't be combined with OR: price < 50 OR _tags:sale"
+    );
+    assert!(String::try_from(filter).is_err());
+
+    let filter = (Filter::facet("a", "1") & Filter::facet("b", "2")) | Filter::facet("c", "3");
+    assert_eq!(filter.to_string(), "(a:1 AND b:2) OR c:3");
+    let err = filter.try_to_string().unwrap_err();
+    assert!(
+        err.message.contains("AND can't be nested in an OR"),
+        "{err}"
+    );
+
+    // Matches nothing, dropping it would match everything else
+    let filter = Filter::facet("a", "1") & Filter::any(vec![]);
+    assert!(filter.try_to_string().is_err());
+    assert!(Filter::all(vec![]).try_to_string().is_err());
+
+    let err = Filter::numeric("price", NumericOp::Lt, f64::NAN)
+        .try_to_string()
+        .unwrap_err();
+    assert_eq!(err.message, "numeric values must be finite: price < NaN");
+    assert!(Filter::range("price", 0, f64::INFINITY)
+        .try_to_string()
+        .is_err());
+    assert!(
+        (Filter::tag("sale") & !Filter::numeric("rating", NumericOp::Gt, f64::NEG_INFINITY))
+            .try_to_string()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_filter_large_or_of_ands_is_rejected_quickly() {
+    let filter = Filter::any(
+        (0..30).map(|i| Filter::facet(format!("a{i}"), "x") & Filter::facet(format!("b{i}"), "y")),
+    );
+
+    let started = std::time::Instant::now();
+    assert!(filter.try_to_string().is_err());
+    assert!(filter.to_string().len() < 1000);
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[test]
+fn test_filter_parse_round_trips() {
+    let inputs = [
+        r#"brand:"Acme Corp" AND (price < 50 OR rating >= 4) AND NOT color:red"#,
+        "price:10 TO 20 OR rating > 4.5",
+        r#"title:"say \"hi\"" AND NOT a:1 AND NOT b:2"#,
+        "brand:Acme<score=2> OR brand:Globex<score=1>",
+        "temperature != -5",
+    ];
+    for input in inputs {
+        let parsed: Filter = input.parse().expect(input);
+        assert_eq!(parsed.try_to_string().unwrap(), input);
+        assert_eq!(parsed.to_string(), input);
+        assert_eq!(parsed.to_string().parse::<Filter>().unwrap(), parsed);
+    }
+
+    let parsed: Filter = "a:1 AND b:2 OR c:3".parse().unwrap();
+    assert_eq!(
+        parsed,
+        (Filter::facet("a", "1") & Filter::facet("b", "2")) | Filter::facet("c", "3")
+    );
+    assert_eq!(parsed.to_string(), "(a:1 AND b:2) OR c:3");
+}
+
+#[test]
+fn test_filter_parse_reports_position() {
+    let err = "brand:Acme AND".parse::<Filter>().unwrap_err();
+    assert_eq!(err.position, 14);
+
+    let err = "brand:\"Acme".parse::<Filter>().unwrap_err();
+    assert_eq!(err.position, 6);
+    assert!(err.to_string().contains("unterminated"));
+
+    let err = "(a:1 OR b:2".parse::<Filter>().unwrap_err();
+    assert!(err.message.contains("')'"));
+
+    let err = "price < cheap".parse::<Filter>().unwrap_err();
+    assert_eq!(err.position, 8);
+}
+
+#[test]
+fn test_filter_parse_limits_nesting() {
+    let nested = |depth: usize| format!("{}a:b{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(
+        nested(64).parse::<Filter>().unwrap(),
+        Filter::facet("a", "b")
+    );
+
+    // Would overflow the stack without a limit
+    let err = nested(5000).parse::<Filter>().unwrap_err();
+    assert_eq!(err.position, 64);
+    assert_eq!(err.message, "filter nested deeper than 64 levels");
+
+    let err = format!("{}a:b", "NOT ".repeat(5000))
+        .parse::<Filter>()
+        .unwrap_err();
+    assert!(err.message.contains("nested deeper"), "{err}");
+}
+
+#[test]
+fn test_facet_filters_render_nested_arrays() {
+    let filters = FacetFilters::new()
+        .all(FacetFilter::eq("brand", "Acme"))
+        .all(FacetFilter::not_eq("color", "red"))
+        .any([FacetFilter::eq("size", "M"), FacetFilter::eq("size", "-L")]);
+    let optional = FacetFilters::new().any([
+        FacetFilter::eq("brand", "Acme").score(2),
+        FacetFilter::eq("brand", "Globex"),
+    ]);
+
+    let request = RecommendRequest::related_products("products", "obj-1")
+        .filters(
+            Filter::numeric("price", NumericOp::Lt, 100)
+                .try_to_string()
+                .unwrap(),
+        )
+        .facet_filters(filters)
+        .optional_filters(optional);
+
+    assert_eq!(
+        serde_json::to_value(&request.query_parameters).unwrap(),
+        json!({
+            "filters": "price < 100",
+            "facetFilters": ["brand:Acme", "color:-red", ["size:M", "size:\\-L"]],
+            "optionalFilters": [["brand:Acme<score=2>", "brand:Globex"]]
+        })
+    );
+
+    let facets = TrendingFacetsRequest::new("products", "category")
+        .filters(Filter::tag("new").try_to_string().unwrap());
+    assert_eq!(
+        facets.query_parameters.unwrap().filters.as_deref(),
+        Some("_tags:new")
+    );
+}
+
+#[test]
+fn test_facet_filter_parse_round_trips() {
+    for input in [
+        "brand:Acme",
+        "color:-red",
+        "size:\\-L",
+        "brand:Acme<score=2>",
+    ] {
+        let parsed: FacetFilter = input.parse().expect(input);
+        assert_eq!(parsed.to_string(), input);
+    }
+
+    let parsed: FacetFilter = "size:\\-L".parse().unwrap();
+    assert_eq!(parsed.value, "-L");
+    assert!(!parsed.negated);
+
+    assert!("no-colon".parse::<FacetFilter>().is_err());
+}
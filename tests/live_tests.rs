@@ -26,7 +26,9 @@ async fn live_smoke_get_recommendations() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let result = client
@@ -109,7 +111,9 @@ async fn live_get_recommendations() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let result = client
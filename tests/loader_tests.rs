@@ -0,0 +1,104 @@
+use algolia_recommend_rs::{
+    Error, ExponentialBackoff, LoaderOptions, RecommendClient, RecommendRequest,
+};
+use futures_util::future::join_all;
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use std::time::Duration;
+
+mod common;
+
+use common::{mock_batch, request, PATH};
+
+#[derive(Debug, Deserialize)]
+struct Product {}
+
+fn client(server: &MockServer) -> RecommendClient {
+    RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .build()
+        .expect("client builds")
+}
+
+#[tokio::test]
+async fn test_loader_batches_concurrent_requests() {
+    let server = MockServer::start_async().await;
+    let mock = mock_batch(&server, &["a", "b", "c"]);
+
+    let loader = client(&server).loader(LoaderOptions::new().window(Duration::from_millis(50)));
+    let results = join_all(["a", "b", "c"].map(|id| {
+        let loader = loader.clone();
+        async move { loader.load::<Product>(request(id)).await }
+    }))
+    .await;
+
+    mock.assert();
+    let ids: Vec<String> = results
+        .into_iter()
+        .map(|r| r.expect("request ok").hits[0].object_id.clone())
+        .collect();
+    assert_eq!(ids, ["rec-a", "rec-b", "rec-c"]);
+}
+
+#[tokio::test]
+async fn test_loader_sends_full_batches_right_away() {
+    let server = MockServer::start_async().await;
+    let first = mock_batch(&server, &["a", "b"]);
+    let second = mock_batch(&server, &["c"]);
+
+    // The window is never reached by the first batch
+    let loader = client(&server).loader(
+        LoaderOptions::new()
+            .window(Duration::from_millis(200))
+            .max_batch_size(2),
+    );
+    let started = std::time::Instant::now();
+    // Each load with the time it took to resolve
+    let timed = |object_id| {
+        let load = loader.load::<Product>(request(object_id));
+        async move { (load.await, started.elapsed()) }
+    };
+    let ((a, a_took), (b, _), (c, c_took)) = tokio::join!(timed("a"), timed("b"), timed("c"));
+    assert_eq!(a.expect("request ok").hits[0].object_id, "rec-a");
+    assert!(b.is_ok());
+
+    first.assert();
+    second.assert();
+    assert_eq!(c.expect("request ok").hits[0].object_id, "rec-c");
+    assert!(a_took < Duration::from_millis(100), "{a_took:?}");
+    assert!(c_took >= Duration::from_millis(200), "{c_took:?}");
+}
+
+#[tokio::test]
+async fn test_loader_failures() {
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path(PATH);
+        then.status(503).body("unavailable");
+    });
+
+    let loader = client(&server).loader(LoaderOptions::new().window(Duration::from_millis(50)));
+    let (invalid, a, b) = tokio::join!(
+        loader.load::<Product>(RecommendRequest::related_products("", "x")),
+        loader.load::<Product>(request("a")),
+        loader.load::<Product>(request("b")),
+    );
+
+    // Rejected before it joined a batch
+    assert!(matches!(invalid, Err(Error::Validation { .. })));
+    for outcome in [a, b] {
+        let err = outcome.expect_err("upstream is down");
+        assert!(matches!(err, Error::Coalesced(_)), "{err:?}");
+        assert!(err.is_retryable());
+    }
+    assert_eq!(mock.calls(), 1);
+
+    // Alone in its batch, a request gets the error as is
+    let err = loader
+        .load::<Product>(request("c"))
+        .await
+        .expect_err("upstream is down");
+    assert!(matches!(err, Error::AllHostsFailed { .. }), "{err:?}");
+}
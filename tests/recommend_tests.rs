@@ -39,7 +39,9 @@ async fn test_get_recommendations_excludes_trending_facets_and_parses_hits() {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         },
         RecommendRequest {
             index_name: "products".to_string(),
@@ -48,7 +50,9 @@ async fn test_get_recommendations_excludes_trending_facets_and_parses_hits() {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         },
         RecommendRequest {
             index_name: "products".to_string(),
@@ -57,7 +61,9 @@ async fn test_get_recommendations_excludes_trending_facets_and_parses_hits() {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         },
         RecommendRequest {
             index_name: "products".to_string(),
@@ -66,7 +72,9 @@ async fn test_get_recommendations_excludes_trending_facets_and_parses_hits() {
             threshold: 0,
             max_recommendations: None,
             facet_name: None,
+            facet_value: None,
             query_parameters: None,
+            fallback_parameters: None,
         },
     ];
 
@@ -137,7 +145,9 @@ async fn test_non_2xx_api_error_is_mapped() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let err = client
@@ -172,7 +182,9 @@ async fn test_malformed_json_yields_serde_error() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let err = client
@@ -221,7 +233,9 @@ async fn test_retry_on_5xx_then_succeed_on_next_host() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let resp = client
@@ -263,7 +277,9 @@ async fn test_retry_on_network_error_then_succeed() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let resp = client
@@ -309,7 +325,9 @@ async fn test_non_retryable_4xx_does_not_try_next_host() {
         threshold: 0,
         max_recommendations: None,
         facet_name: None,
+        facet_value: None,
         query_parameters: None,
+        fallback_parameters: None,
     }];
 
     let err = client
@@ -325,3 +343,1003 @@ async fn test_non_retryable_4xx_does_not_try_next_host() {
     assert_eq!(m1.calls(), 1);
     assert_eq!(m2.calls(), 0);
 }
+
+#[tokio::test]
+async fn test_builder_with_shared_http_client() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .header("x-algolia-application-id", "APPID")
+            .header("x-algolia-api-key", "KEY");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[{"objectID":"shared"}]}]}"#);
+    });
+
+    let http = reqwest::Client::new();
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .http_client(http)
+        .build()
+        .expect("client builds");
+
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let resp = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(resp.results[0].hits[0].object_id, "shared");
+}
+
+#[tokio::test]
+async fn test_builder_timeout_applies_to_requests() {
+    let server = MockServer::start();
+
+    let _mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .delay(std::time::Duration::from_millis(500))
+            .header("content-type", "application/json")
+            .body(r#"{"results":[]}"#);
+    });
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .timeout(std::time::Duration::from_millis(50))
+        .build()
+        .expect("client builds");
+
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let err = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should time out");
+
+    let msg = format!("{err}");
+    assert!(msg.contains("request timed out"), "unexpected error: {msg}");
+}
+
+#[tokio::test]
+async fn test_failed_host_is_skipped_until_down_ttl_expires() {
+    let primary = MockServer::start();
+    let fallback = MockServer::start();
+
+    let m1 = primary.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(503)
+            .header("content-type", "application/json")
+            .body(r#"{"message":"unavailable"}"#);
+    });
+
+    let m2 = fallback.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .hosts(vec![primary.base_url(), fallback.base_url()])
+        .build()
+        .expect("client builds");
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::HostState;
+
+    for _ in 0..3 {
+        client
+            .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+            .await
+            .expect("request ok");
+    }
+
+    // The cursor wraps back to the primary on the third call, but it is down
+    assert_eq!(m1.calls(), 1);
+    assert_eq!(m2.calls(), 3);
+
+    let statuses = client.host_statuses();
+    assert_eq!(statuses[0].state, HostState::Down);
+    assert!(!statuses[0].available);
+    assert_eq!(statuses[1].state, HostState::Up);
+}
+
+#[tokio::test]
+async fn test_timed_out_host_is_tracked_with_retry_count() {
+    let slow = MockServer::start();
+    let fast = MockServer::start();
+
+    let _slow = slow.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .delay(std::time::Duration::from_millis(500))
+            .header("content-type", "application/json")
+            .body(r#"{"results":[]}"#);
+    });
+
+    let _fast = fast.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .hosts(vec![slow.base_url(), fast.base_url()])
+        .timeout(std::time::Duration::from_millis(50))
+        .build()
+        .expect("client builds");
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::HostState;
+
+    client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect("request ok on second host");
+
+    let statuses = client.host_statuses();
+    assert_eq!(statuses[0].state, HostState::TimedOut);
+    assert_eq!(statuses[0].retry_count, 1);
+    assert_eq!(statuses[1].state, HostState::Up);
+}
+
+#[tokio::test]
+async fn test_max_attempts_is_independent_of_host_count() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(500)
+            .header("content-type", "application/json")
+            .body(r#"{"message":"server error"}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::ExponentialBackoff;
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(ExponentialBackoff::no_delay(3))
+        .build()
+        .expect("client builds");
+
+    client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should fail after retries");
+    assert_eq!(mock.calls(), 3);
+
+    // Per-call override shares the host state but not the policy
+    client
+        .with_retry_policy(ExponentialBackoff::no_delay(1))
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should fail without retrying");
+    assert_eq!(mock.calls(), 4);
+}
+
+#[tokio::test]
+async fn test_rate_limited_call_waits_for_retry_after() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(429)
+            .header("content-type", "application/json")
+            .header("retry-after", "1")
+            .body(r#"{"message":"too many requests"}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::{ExponentialBackoff, RetryPolicy};
+
+    let policy = ExponentialBackoff::default()
+        .with_max_attempts(2)
+        .max_delay(std::time::Duration::from_secs(2));
+    assert_eq!(policy.max_attempts(), 2);
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(policy)
+        .build()
+        .expect("client builds");
+
+    let started = std::time::Instant::now();
+    client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should stay rate limited");
+
+    assert_eq!(mock.calls(), 2);
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+    // Being rate limited says nothing about the host
+    let statuses = client.host_statuses();
+    assert_eq!(statuses[0].state, algolia_recommend_rs::HostState::Up);
+    assert!(statuses[0].available);
+}
+
+#[test]
+fn test_debug_output_redacts_api_key() {
+    let client = RecommendClient::with_base_url("APPID", "SUPER-SECRET-KEY", "http://localhost");
+
+    let debug = format!("{client:?}");
+    assert!(debug.contains("APPID"));
+    assert!(!debug.contains("SUPER-SECRET-KEY"), "leaked key: {debug}");
+
+    let builder = RecommendClient::builder("APPID", "SUPER-SECRET-KEY");
+    assert!(!format!("{builder:?}").contains("SUPER-SECRET-KEY"));
+}
+
+#[test]
+fn test_invalid_credentials_fail_at_construction() {
+    use algolia_recommend_rs::Error;
+
+    let err = RecommendClient::builder("APPID\n", "KEY")
+        .build()
+        .expect_err("newline is not a valid header value");
+    assert!(matches!(err, Error::InvalidHeader { name } if name == "x-algolia-application-id"));
+
+    let err = RecommendClient::builder("APPID", "KEY\r\n")
+        .build()
+        .expect_err("newline is not a valid header value");
+    assert!(matches!(&err, Error::InvalidHeader { name } if name == "x-algolia-api-key"));
+    assert!(!format!("{err}").contains("KEY"));
+
+    let err = RecommendClient::try_new("APPID\n", "KEY").expect_err("invalid app ID");
+    assert!(matches!(err, Error::InvalidHeader { .. }));
+    let err = RecommendClient::try_with_base_url("APPID", "KEY\n", "http://localhost")
+        .expect_err("invalid API key");
+    assert!(matches!(err, Error::InvalidHeader { .. }));
+}
+
+#[test]
+fn test_invalid_hosts_fail_at_construction() {
+    use algolia_recommend_rs::Error;
+
+    let err = RecommendClient::builder("APPID", "KEY")
+        .hosts(vec![])
+        .build()
+        .expect_err("no host");
+    assert!(matches!(&err, Error::InvalidHost(message) if message == "no host given"));
+
+    let err = RecommendClient::try_with_base_url("APPID", "KEY", "localhost:8080/api")
+        .expect_err("no scheme");
+    assert!(matches!(err, Error::InvalidHost(_)));
+    let err = RecommendClient::try_with_custom_host("APPID", "KEY", "my host")
+        .expect_err("space in host");
+    assert!(matches!(err, Error::InvalidHost(_)));
+    let err = RecommendClient::try_with_hosts(
+        "APPID",
+        "KEY",
+        vec![String::from("https://a.example"), String::from("https://")],
+    )
+    .expect_err("second host has no name");
+    assert_eq!(
+        err.to_string(),
+        r#"invalid host: "https://" is not a base URL"#
+    );
+    assert!(!err.is_retryable());
+    let err = RecommendClient::try_with_base_url("APPID", "KEY", "http://localhost:8080/api?x=1")
+        .expect_err("base URL with a query");
+    assert_eq!(
+        err.to_string(),
+        r#"invalid host: "http://localhost:8080/api?x=1" is not a base URL"#
+    );
+
+    RecommendClient::try_with_custom_host("APPID", "KEY", "recommend.example.com")
+        .expect("valid host");
+}
+
+#[tokio::test]
+async fn test_trailing_slash_of_a_base_url_is_dropped() {
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    let base_url = format!("{}/", server.base_url());
+    let client = RecommendClient::with_base_url("APPID", "KEY", &base_url);
+    client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(client.host_statuses()[0].url, server.base_url());
+}
+
+#[tokio::test]
+async fn test_path_prefix_of_a_base_url_is_kept() {
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/algolia/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    // Behind a reverse proxy serving Algolia under /algolia
+    let base_url = format!("{}/algolia/", server.base_url());
+    let client = RecommendClient::with_base_url("APPID", "KEY", &base_url);
+    client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect("request ok");
+
+    mock.assert();
+}
+
+#[tokio::test]
+async fn test_request_options_add_headers_and_query_params() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .query_param("x-algolia-agent", "my-service")
+            .header("x-algolia-api-key", "KEY")
+            .header("x-forwarded-for", "10.0.0.1")
+            .header("x-algolia-usertoken", "user-42");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::RequestOptions;
+
+    let options = RequestOptions::new()
+        .forwarded_for("10.0.0.1")
+        .user_token("user-42")
+        .query_param("x-algolia-agent", "my-service");
+
+    client
+        .get_recommendations_with_options::<Product>(
+            vec![RecommendRequest::trending_items("products")],
+            &options,
+        )
+        .await
+        .expect("request ok");
+
+    mock.assert();
+}
+
+#[tokio::test]
+async fn test_request_options_timeout_and_invalid_header() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .delay(std::time::Duration::from_millis(500))
+            .header("content-type", "application/json")
+            .body(r#"{"results":[]}"#);
+    });
+
+    use algolia_recommend_rs::{Error, ExponentialBackoff, RequestOptions};
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .build()
+        .expect("client builds");
+
+    let err = client
+        .get_trending_facets_with_options(
+            vec![TrendingFacetsRequest::new("products", "category")],
+            &RequestOptions::new().timeout(std::time::Duration::from_millis(50)),
+        )
+        .await
+        .expect_err("should time out");
+    assert!(format!("{err}").contains("request timed out"));
+
+    let err = client
+        .get_trending_facets_with_options(
+            vec![TrendingFacetsRequest::new("products", "category")],
+            &RequestOptions::new().header("x-custom", "line\nbreak"),
+        )
+        .await
+        .expect_err("invalid header value");
+    assert!(matches!(err, Error::InvalidHeader { name } if name == "x-custom"));
+    assert_eq!(mock.calls(), 1);
+}
+
+#[tokio::test]
+async fn test_errors_are_classified_by_status() {
+    let server = MockServer::start();
+
+    let _mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(404)
+            .header("content-type", "application/json")
+            .body(r#"{"message":"index does not exist"}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::Error;
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let err = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should error");
+
+    match &err {
+        Error::NotFound { message, .. } => {
+            assert_eq!(message.as_deref(), Some("index does not exist"))
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_exhausted_hosts_report_every_attempt() {
+    let first = MockServer::start();
+    let second = MockServer::start();
+
+    let _m1 = first.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(429)
+            .header("content-type", "application/json")
+            .header("retry-after", "0")
+            .body(r#"{"message":"slow down"}"#);
+    });
+
+    let _m2 = second.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(502)
+            .header("content-type", "application/json")
+            .body(r#"{"message":"bad gateway"}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::{Error, ExponentialBackoff};
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .hosts(vec![first.base_url(), second.base_url()])
+        .retry_policy(ExponentialBackoff::no_delay(2))
+        .build()
+        .expect("client builds");
+
+    let err = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+        .await
+        .expect_err("should error");
+    assert!(err.is_retryable());
+
+    let Error::AllHostsFailed { attempts } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0].host, first.base_url());
+    assert!(matches!(
+        attempts[0].error,
+        Error::RateLimited {
+            retry_after: Some(d),
+            ..
+        } if d.is_zero()
+    ));
+    assert_eq!(attempts[1].host, second.base_url());
+    assert!(matches!(attempts[1].error, Error::Api { status: 502, .. }));
+}
+
+#[tokio::test]
+async fn test_trending_facets_model_mismatch_is_a_validation_error() {
+    use algolia_recommend_rs::Error;
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", "http://127.0.0.1:9");
+
+    let mut wrong = TrendingFacetsRequest::new("products", "brand");
+    wrong.model = Model::TrendingItems;
+
+    let err = client
+        .get_trending_facets(vec![
+            TrendingFacetsRequest::new("products", "category"),
+            wrong,
+        ])
+        .await
+        .expect_err("should be rejected before sending");
+
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].index, Some(1));
+    assert_eq!(errors[0].field, "model");
+}
+
+#[tokio::test]
+async fn test_invalid_requests_are_rejected_with_every_offending_index() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]},{"hits":[]},{"hits":[]}]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::Error;
+
+    let mut missing_object_id = RecommendRequest::bought_together("products", "obj-1");
+    missing_object_id.object_id = None;
+    let mut bad_threshold = RecommendRequest::trending_items("products");
+    bad_threshold.threshold = 101;
+    let requests = vec![
+        missing_object_id,
+        RecommendRequest::trending_items("products"),
+        bad_threshold,
+    ];
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations::<Product>(requests.clone())
+        .await
+        .expect_err("should be rejected before sending");
+
+    let Error::Validation { errors } = &err else {
+        panic!("unexpected error: {err:?}");
+    };
+    let offending: Vec<_> = errors.iter().map(|e| (e.index, e.field.as_str())).collect();
+    assert_eq!(
+        offending,
+        vec![(Some(0), "objectID"), (Some(2), "threshold")]
+    );
+    assert!(format!("{err}").contains("requests[2].threshold"));
+    assert_eq!(mock.calls(), 0);
+
+    // Opting out leaves the decision to the API
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .validate_requests(false)
+        .build()
+        .expect("client builds");
+    client
+        .get_recommendations::<Product>(requests)
+        .await
+        .expect("request sent as is");
+    assert_eq!(mock.calls(), 1);
+}
+
+#[test]
+fn test_validate_checks_model_specific_fields_and_limits() {
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    assert!(RecommendRequest::related_products("products", "obj-1")
+        .validate()
+        .is_ok());
+
+    let mut facets = RecommendRequest::trending_items("");
+    facets.model = Model::TrendingFacets;
+    facets.max_recommendations = Some(0);
+    let fields: Vec<_> = facets
+        .validate()
+        .expect_err("invalid")
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+    assert_eq!(fields, vec!["indexName", "maxRecommendations", "facetName"]);
+
+    let mut facets = TrendingFacetsRequest::new("products", "");
+    facets.threshold = -1;
+    let fields: Vec<_> = facets
+        .validate()
+        .expect_err("invalid")
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+    assert_eq!(fields, vec!["threshold", "facetName"]);
+}
+
+#[test]
+fn test_fluent_and_type_state_builders_produce_the_same_request() {
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::SearchParams;
+    use serde_json::json;
+
+    let fluent = RecommendRequest::bought_together("products", "obj-1")
+        .threshold(40)
+        .max(10)
+        .query_params(SearchParams::new().filters("brand:Acme"));
+    let built = RecommendRequest::builder("products")
+        .threshold(40)
+        .bought_together("obj-1")
+        .max(10)
+        .query_params(SearchParams::new().filters("brand:Acme"))
+        .build();
+
+    let expected = json!({
+        "indexName": "products",
+        "model": "bought-together",
+        "objectID": "obj-1",
+        "threshold": 40,
+        "maxRecommendations": 10,
+        "queryParameters": { "filters": "brand:Acme" }
+    });
+    assert_eq!(serde_json::to_value(&fluent).unwrap(), expected);
+    assert_eq!(serde_json::to_value(&built).unwrap(), expected);
+
+    let trending = RecommendRequest::builder("products")
+        .trending_items()
+        .build();
+    assert_eq!(trending.model, Model::TrendingItems);
+    assert_eq!(trending.object_id, None);
+
+    let facets = TrendingFacetsRequest::builder("products")
+        .max(5)
+        .facet_name("category")
+        .threshold(10)
+        .build();
+    assert_eq!(
+        serde_json::to_value(&facets).unwrap(),
+        json!({
+            "model": "trending-facets",
+            "indexName": "products",
+            "facetName": "category",
+            "threshold": 10,
+            "maxRecommendations": 5
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(
+            TrendingFacetsRequest::new("products", "category")
+                .threshold(10)
+                .max(5)
+        )
+        .unwrap(),
+        serde_json::to_value(&facets).unwrap()
+    );
+}
+
+#[test]
+fn test_search_params_serialize_with_api_names_and_keep_unknown_keys() {
+    use algolia_recommend_rs::{FilterList, SearchParams};
+    use serde_json::json;
+
+    let params = SearchParams::new()
+        .filters("price < 50")
+        .facet_filters(vec![
+            FilterList::from("brand:Acme"),
+            FilterList::from(vec!["color:red", "color:blue"]),
+        ])
+        .attributes_to_retrieve(["title", "price"])
+        .click_analytics(true)
+        .user_token("user-42")
+        .rule_contexts(["homepage"])
+        .extra("aroundRadius", 1000);
+
+    let value = serde_json::to_value(&params).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "filters": "price < 50",
+            "facetFilters": ["brand:Acme", ["color:red", "color:blue"]],
+            "attributesToRetrieve": ["title", "price"],
+            "clickAnalytics": true,
+            "userToken": "user-42",
+            "ruleContexts": ["homepage"],
+            "aroundRadius": 1000
+        })
+    );
+
+    let parsed: SearchParams = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed, params);
+    assert_eq!(parsed.extra.get("aroundRadius"), Some(&json!(1000)));
+}
+
+#[tokio::test]
+async fn test_fallback_parameters_are_sent_for_models_that_accept_them() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .json_body(serde_json::json!({
+                "requests": [{
+                    "indexName": "products",
+                    "model": "related-products",
+                    "objectID": "obj-1",
+                    "threshold": 0,
+                    "fallbackParameters": { "filters": "category:shoes" }
+                }]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[{"objectID":"from-fallback"}]}]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::{Error, SearchParams};
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let resp = client
+        .get_recommendations::<Product>(vec![RecommendRequest::related_products(
+            "products", "obj-1",
+        )
+        .fallback_params(SearchParams::new().filters("category:shoes"))])
+        .await
+        .expect("request ok");
+    mock.assert();
+    assert_eq!(resp.results[0].hits[0].object_id, "from-fallback");
+
+    let err = client
+        .get_recommendations::<Product>(vec![RecommendRequest::bought_together(
+            "products", "obj-1",
+        )
+        .fallback_params(SearchParams::new().filters("category:shoes"))])
+        .await
+        .expect_err("bought-together has no fallback");
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors[0].field, "fallbackParameters");
+}
+
+#[tokio::test]
+async fn test_recommended_for_you_sends_user_token() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .header("x-algolia-application-id", "APPID")
+            .header("x-algolia-api-key", "KEY")
+            .json_body(serde_json::json!({
+                "requests": [{
+                    "indexName": "products",
+                    "model": "recommended-for-you",
+                    "threshold": 20,
+                    "queryParameters": { "userToken": "user-42" }
+                }]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[{"objectID":"for-you","_score":87.5}]}]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let resp = client
+        .get_recommendations::<Product>(vec![RecommendRequest::recommended_for_you(
+            "products", "user-42",
+        )
+        .threshold(20)])
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(resp.results[0].hits[0].object_id, "for-you");
+    assert_eq!(resp.results[0].hits[0].score, Some(87.5));
+
+    let built = RecommendRequest::builder("products")
+        .threshold(20)
+        .recommended_for_you("user-42")
+        .build();
+    assert_eq!(
+        serde_json::to_value(&built).unwrap(),
+        serde_json::to_value(
+            RecommendRequest::recommended_for_you("products", "user-42").threshold(20)
+        )
+        .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_recommended_for_you_requires_user_token() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::Error;
+
+    let mut request = RecommendRequest::recommended_for_you("products", "user-42");
+    request.query_parameters = None;
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products"), request])
+        .await
+        .expect_err("should be rejected before sending");
+
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].index, Some(1));
+    assert_eq!(errors[0].field, "queryParameters.userToken");
+    assert_eq!(mock.calls(), 0);
+}
+
+#[test]
+fn test_unknown_models_round_trip_as_other() {
+    use algolia_recommend_rs::models::RecommendRequest;
+    use serde_json::json;
+
+    let stored = json!({
+        "indexName": "products",
+        "model": "frequently-viewed",
+        "objectID": "obj-1",
+        "threshold": 10
+    });
+    let request: RecommendRequest = serde_json::from_value(stored.clone()).expect("parses");
+    assert_eq!(request.model, Model::Other("frequently-viewed".to_string()));
+    assert_eq!(serde_json::to_value(&request).unwrap(), stored);
+    // Left for the API to judge
+    assert!(request.validate().is_ok());
+
+    assert_eq!(
+        serde_json::from_value::<Model>(json!("looking-similar")).unwrap(),
+        Model::LookingSimilar
+    );
+    assert_eq!(
+        serde_json::to_value(Model::RecommendedForYou).unwrap(),
+        json!("recommended-for-you")
+    );
+    assert_eq!(
+        "trending-facets".parse::<Model>(),
+        Ok(Model::TrendingFacets)
+    );
+    assert_eq!(Model::from("new-model").to_string(), "new-model");
+}
+
+#[test]
+fn test_other_holding_a_known_name_is_that_model() {
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let model = Model::Other("bought-together".to_string());
+    assert_eq!(model, Model::BoughtTogether);
+    assert!(model.is_known());
+    assert!(model.requires_object_id());
+
+    let mut request = RecommendRequest::trending_items("products");
+    request.model = model;
+    let errors = request.validate().expect_err("objectID is required");
+    assert_eq!(errors[0].field, "objectID");
+}
+
+#[tokio::test]
+async fn test_trending_facets_reject_unknown_models() {
+    use algolia_recommend_rs::{Error, ExponentialBackoff};
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url("http://127.0.0.1:9")
+        .retry_policy(ExponentialBackoff::no_delay(1))
+        .validate_requests(false)
+        .build()
+        .expect("client builds");
+
+    let mut request = TrendingFacetsRequest::new("products", "category");
+    request.model = Model::from("trending-brands");
+
+    let err = client
+        .get_trending_facets(vec![request])
+        .await
+        .expect_err("should be rejected before sending");
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert!(errors[0].message.contains("got trending-brands"));
+
+    // Built by hand with a known name, it is trending facets all the same
+    let mut request = TrendingFacetsRequest::new("products", "category");
+    request.model = Model::Other("trending-facets".to_string());
+    let err = client
+        .get_trending_facets(vec![request])
+        .await
+        .expect_err("nothing listens there");
+    assert!(!matches!(err, Error::Validation { .. }), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_trending_items_for_facet_sends_facet_name_and_value() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/*/recommendations")
+            .json_body(serde_json::json!({
+                "requests": [{
+                    "indexName": "products",
+                    "model": "trending-items",
+                    "threshold": 10,
+                    "facetName": "category",
+                    "facetValue": "shoes"
+                }]
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[{"objectID":"sneaker","_score":64.0}]}]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let resp = client
+        .get_recommendations::<Product>(vec![RecommendRequest::trending_items_for_facet(
+            "products", "category", "shoes",
+        )
+        .threshold(10)])
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(resp.results[0].hits[0].object_id, "sneaker");
+
+    let built = RecommendRequest::builder("products")
+        .threshold(10)
+        .trending_items_for_facet("category", "shoes")
+        .build();
+    assert_eq!(
+        serde_json::to_value(&built).unwrap(),
+        serde_json::to_value(
+            RecommendRequest::trending_items_for_facet("products", "category", "shoes")
+                .threshold(10)
+        )
+        .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_facet_value_requires_trending_items_and_facet_name() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[]}"#);
+    });
+
+    use algolia_recommend_rs::models::RecommendRequest;
+    use algolia_recommend_rs::Error;
+
+    let mut missing_name =
+        RecommendRequest::trending_items_for_facet("products", "category", "shoes");
+    missing_name.facet_name = None;
+    let mut wrong_model = RecommendRequest::related_products("products", "obj-1");
+    wrong_model.facet_value = Some("shoes".to_string());
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .get_recommendations::<Product>(vec![missing_name, wrong_model])
+        .await
+        .expect_err("should be rejected before sending");
+
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].index, Some(0));
+    assert_eq!(errors[0].field, "facetValue");
+    assert_eq!(errors[1].index, Some(1));
+    assert!(errors[1].message.contains("related-products"));
+    assert_eq!(mock.calls(), 0);
+}
@@ -0,0 +1,391 @@
+#![cfg(feature = "redis")]
+
+use algolia_recommend_rs::redis_cache::RedisCache;
+use algolia_recommend_rs::{RecommendCache, RecommendClient, RecommendRequest};
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Deserialize)]
+struct Product {}
+
+type Store = Arc<Mutex<HashMap<Vec<u8>, (Vec<u8>, Option<Instant>)>>>;
+
+// Just enough of redis-server for the cache: AUTH, SELECT, GET, MGET, SET PX, DEL
+struct StandIn {
+    addr: String,
+    store: Store,
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl StandIn {
+    async fn start(password: Option<&'static str>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let store = Store::default();
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let (s, c) = (store.clone(), commands.clone());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve(socket, password, s.clone(), c.clone()));
+            }
+        });
+        Self {
+            addr,
+            store,
+            commands,
+        }
+    }
+
+    fn commands(&self) -> Vec<Vec<String>> {
+        self.commands.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    socket: TcpStream,
+    password: Option<&'static str>,
+    store: Store,
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+) {
+    let mut stream = BufStream::new(socket);
+    let mut authed = password.is_none();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let count: usize = line.trim()[1..].parse().unwrap();
+        let mut args = Vec::new();
+        for _ in 0..count {
+            let mut len = String::new();
+            stream.read_line(&mut len).await.unwrap();
+            let len: usize = len.trim()[1..].parse().unwrap();
+            let mut arg = vec![0; len + 2];
+            stream.read_exact(&mut arg).await.unwrap();
+            arg.truncate(len);
+            args.push(arg);
+        }
+        commands.lock().unwrap().push(
+            args.iter()
+                .map(|a| String::from_utf8_lossy(a).into_owned())
+                .collect(),
+        );
+
+        let reply: Vec<u8> = match args[0].as_slice() {
+            b"AUTH" if Some(args[1].as_slice()) == password.map(str::as_bytes) => {
+                authed = true;
+                b"+OK\r\n".to_vec()
+            }
+            b"AUTH" => b"-WRONGPASS invalid password\r\n".to_vec(),
+            _ if !authed => b"-NOAUTH Authentication required.\r\n".to_vec(),
+            b"SELECT" => b"+OK\r\n".to_vec(),
+            b"GET" => {
+                let store = store.lock().unwrap();
+                match store.get(&args[1]) {
+                    Some((value, expires)) if expires.is_none_or(|e| e > Instant::now()) => {
+                        let mut reply = format!("${}\r\n", value.len()).into_bytes();
+                        reply.extend_from_slice(value);
+                        reply.extend_from_slice(b"\r\n");
+                        reply
+                    }
+                    _ => b"$-1\r\n".to_vec(),
+                }
+            }
+            b"MGET" => {
+                let store = store.lock().unwrap();
+                let mut reply = format!("*{}\r\n", args.len() - 1).into_bytes();
+                for key in &args[1..] {
+                    match store.get(key) {
+                        Some((value, expires)) if expires.is_none_or(|e| e > Instant::now()) => {
+                            reply.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+                            reply.extend_from_slice(value);
+                            reply.extend_from_slice(b"\r\n");
+                        }
+                        _ => reply.extend_from_slice(b"$-1\r\n"),
+                    }
+                }
+                reply
+            }
+            b"SET" => {
+                let expires = match args.get(3).map(Vec::as_slice) {
+                    Some(b"PX") => {
+                        let ms: u64 = String::from_utf8_lossy(&args[4]).parse().unwrap();
+                        Some(Instant::now() + Duration::from_millis(ms))
+                    }
+                    _ => None,
+                };
+                store
+                    .lock()
+                    .unwrap()
+                    .insert(args[1].clone(), (args[2].clone(), expires));
+                b"+OK\r\n".to_vec()
+            }
+            b"DEL" => {
+                let removed = store.lock().unwrap().remove(&args[1]).is_some();
+                format!(":{}\r\n", removed as u8).into_bytes()
+            }
+            _ => b"-ERR unknown command\r\n".to_vec(),
+        };
+        stream.write_all(&reply).await.unwrap();
+        stream.flush().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_redis_cache_is_shared_between_clients() {
+    let redis = StandIn::start(None).await;
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[{"objectID":"rec-1"}]}]}"#);
+    });
+
+    // Two replicas of the same service
+    let replica = |api_key: &str| {
+        RecommendClient::builder("APPID", api_key)
+            .base_url(server.base_url())
+            .cache_backend(RedisCache::new(&redis.addr), Duration::from_secs(60))
+            .build()
+            .expect("client builds")
+    };
+    let (first, second) = (replica("KEY"), replica("KEY"));
+    let request = || vec![RecommendRequest::related_products("products", "obj-1")];
+
+    first
+        .get_recommendations::<Product>(request())
+        .await
+        .expect("request ok");
+    let response = second
+        .get_recommendations::<Product>(request())
+        .await
+        .expect("request ok");
+
+    assert_eq!(mock.calls(), 1);
+    assert_eq!(response.results[0].hits[0].object_id, "rec-1");
+    assert_eq!(second.cache_stats().unwrap().hits, 1);
+
+    // Results fetched with another API key may differ, they are not shared
+    let other = replica("OTHER-KEY");
+    other
+        .get_recommendations::<Product>(request())
+        .await
+        .expect("request ok");
+    assert_eq!(mock.calls(), 2);
+
+    let set = redis
+        .commands()
+        .into_iter()
+        .find(|c| c[0] == "SET")
+        .expect("result stored");
+    assert!(
+        set[1].starts_with("algolia-recommend:APPID:5ca24005b740717ba4f3f6bc48a230700e68c2a4b11ecedb96f169f4efaf1f21:{"),
+        "{}",
+        set[1]
+    );
+    // Prefixed with the time it goes stale, for every replica to agree on
+    let (fresh_until, result) = set[2].split_once('\n').expect("envelope");
+    assert!(fresh_until.parse::<u64>().is_ok(), "{fresh_until}");
+    assert_eq!(result, r#"{"hits":[{"objectID":"rec-1"}]}"#);
+    assert_eq!(set[3..], ["PX", "60000"]);
+}
+
+#[tokio::test]
+async fn test_redis_cache_looks_up_a_batch_in_one_round_trip() {
+    let redis = StandIn::start(None).await;
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]},{"hits":[]},{"hits":[]}]}"#);
+    });
+
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache_backend(RedisCache::new(&redis.addr), Duration::from_secs(60))
+        .build()
+        .expect("client builds");
+    let batch = || {
+        ["a", "b", "c"]
+            .map(|id| RecommendRequest::related_products("products", id))
+            .to_vec()
+    };
+    for _ in 0..2 {
+        client
+            .get_recommendations::<Product>(batch())
+            .await
+            .expect("request ok");
+    }
+
+    assert_eq!(mock.calls(), 1);
+    assert_eq!(client.cache_stats().unwrap().hits, 3);
+    let commands: Vec<(String, usize)> = redis
+        .commands()
+        .into_iter()
+        .map(|c| (c[0].clone(), c.len()))
+        .collect();
+    let expected = [("MGET", 4), ("SET", 5), ("SET", 5), ("SET", 5), ("MGET", 4)];
+    assert_eq!(
+        commands,
+        expected.map(|(command, len)| (command.to_string(), len))
+    );
+}
+
+#[tokio::test]
+async fn test_redis_cache_commands_and_auth() {
+    let redis = StandIn::start(Some("s3cret")).await;
+    let cache = RedisCache::new(&redis.addr)
+        .password("s3cret")
+        .database(2)
+        .key_prefix("test:");
+
+    cache
+        .set("k", b"value".to_vec(), Duration::from_millis(40))
+        .await
+        .expect("set ok");
+    assert_eq!(cache.get("k").await.unwrap(), Some(b"value".to_vec()));
+    cache.delete("k").await.expect("delete ok");
+    assert_eq!(cache.get("k").await.unwrap(), None);
+    assert!(redis.store.lock().unwrap().is_empty());
+
+    cache
+        .set("k", b"value".to_vec(), Duration::from_millis(40))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(cache.get("k").await.unwrap(), None);
+
+    let commands = redis.commands();
+    assert_eq!(commands[0], ["AUTH", "s3cret"]);
+    assert_eq!(commands[1], ["SELECT", "2"]);
+    assert_eq!(commands[2][..2], ["SET", "test:k"]);
+    // One connection, reused
+    assert_eq!(commands.iter().filter(|c| c[0] == "AUTH").count(), 1);
+
+    let wrong = RedisCache::new(&redis.addr).password("nope");
+    let err = wrong.get("k").await.expect_err("auth fails");
+    assert!(err.to_string().contains("WRONGPASS"), "{err}");
+    let debug = format!("{wrong:?}");
+    assert!(!debug.contains("nope"));
+    assert!(debug.contains("password: Some(Secret(***))"), "{debug}");
+}
+
+#[tokio::test]
+async fn test_redis_cache_failures_do_not_fail_calls() {
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]}]}"#);
+    });
+
+    // Nothing listens there once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache_backend(RedisCache::new(addr), Duration::from_secs(60))
+        .build()
+        .expect("client builds");
+
+    for _ in 0..2 {
+        client
+            .get_recommendations::<Product>(vec![RecommendRequest::trending_items("products")])
+            .await
+            .expect("served without the cache");
+    }
+
+    assert_eq!(mock.calls(), 2);
+    let stats = client.cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.errors), (0, 2, 4));
+}
+
+#[tokio::test]
+async fn test_redis_cache_that_never_replies_does_not_hold_up_calls() {
+    let server = MockServer::start_async().await;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/1/indexes/*/recommendations");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"results":[{"hits":[]},{"hits":[]},{"hits":[]}]}"#);
+    });
+
+    // Accepts connections and reads commands, but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let mut sockets = Vec::new();
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            sockets.push(socket);
+        }
+    });
+    let client = RecommendClient::builder("APPID", "KEY")
+        .base_url(server.base_url())
+        .cache_backend(
+            RedisCache::new(addr).timeout(Duration::from_millis(200)),
+            Duration::from_secs(60),
+        )
+        .build()
+        .expect("client builds");
+
+    let started = Instant::now();
+    let calls = (0..10).map(|i| {
+        client.get_recommendations::<Product>(vec![
+            RecommendRequest::related_products("products", format!("a-{i}")),
+            RecommendRequest::related_products("products", format!("b-{i}")),
+            RecommendRequest::related_products("products", format!("c-{i}")),
+        ])
+    });
+    for response in futures_util::future::join_all(calls).await {
+        response.expect("served without the cache");
+    }
+
+    // Waiting in turn for each of the 60 commands would take 12s
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        started.elapsed()
+    );
+    assert_eq!(mock.calls(), 10);
+    let stats = client.cache_stats().unwrap();
+    assert_eq!(stats.misses, 30);
+    assert!(stats.errors > 0);
+}
+
+#[tokio::test]
+async fn test_redis_cache_rejects_oversized_values() {
+    // Claims a value of 1 TB for any command
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut stream = BufStream::new(socket);
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        stream.write_all(b"$1000000000000\r\n").await.unwrap();
+        stream.flush().await.unwrap();
+        // Keep the connection open, the client must not wait for the value
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let cache = RedisCache::new(addr);
+    let started = Instant::now();
+    let err = cache.get("k").await.expect_err("value too large");
+    assert!(err.to_string().contains("malformed reply"), "{err}");
+    assert!(started.elapsed() < Duration::from_millis(500));
+}
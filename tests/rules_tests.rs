@@ -0,0 +1,304 @@
+use algolia_recommend_rs::models::Model;
+use algolia_recommend_rs::rules::{Condition, Consequence, RecommendRule, SearchRulesParams};
+use algolia_recommend_rs::RecommendClient;
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_search_recommend_rules_parses_typed_rules() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/products/related-products/recommend/rules/search")
+            .header("x-algolia-application-id", "APPID")
+            .header("x-algolia-api-key", "KEY")
+            .json_body(json!({ "query": "shoes", "hitsPerPage": 10 }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "hits": [{
+                    "objectID": "promote-sneakers",
+                    "condition": { "filters": "category:shoes", "context": "summer" },
+                    "consequence": {
+                        "promote": [{ "objectID": "sneaker-1", "position": 0 }],
+                        "hide": [{ "objectID": "boot-7" }],
+                        "params": {
+                            "filters": "brand:Acme",
+                            "automaticFacetFilters": [{ "facet": "color", "negative": true }]
+                        }
+                    },
+                    "description": "Sneakers first",
+                    "enabled": true,
+                    "_metadata": { "lastUpdate": "2024-05-01T10:00:00Z" }
+                }],
+                "nbHits": 1,
+                "page": 0,
+                "nbPages": 1
+            }));
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let resp = client
+        .search_recommend_rules(
+            "products",
+            &Model::RelatedProducts,
+            &SearchRulesParams::new().query("shoes").hits_per_page(10),
+        )
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(resp.nb_hits, 1);
+    let rule = &resp.hits[0];
+    assert_eq!(
+        rule.metadata.as_ref().unwrap().last_update.as_deref(),
+        Some("2024-05-01T10:00:00Z")
+    );
+
+    let expected = RecommendRule::new("promote-sneakers")
+        .condition(Condition::new().filters("category:shoes").context("summer"))
+        .consequence(
+            Consequence::new()
+                .promote("sneaker-1", 0)
+                .hide("boot-7")
+                .filters("brand:Acme")
+                .automatic_facet_filter("color", true),
+        )
+        .description("Sneakers first")
+        .enabled(true);
+    assert_eq!(
+        RecommendRule {
+            metadata: None,
+            ..rule.clone()
+        },
+        expected
+    );
+}
+
+#[tokio::test]
+async fn test_get_and_delete_recommend_rule_encode_path_segments() {
+    let server = MockServer::start();
+
+    let get = server.mock(|when, then| {
+        when.method(GET)
+            .path("/1/indexes/my%20index/bought-together/recommend/rules/rule%2F1%3Fx");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"objectID":"rule/1?x","enabled":false}"#);
+    });
+    let delete = server.mock(|when, then| {
+        when.method(DELETE)
+            .path("/1/indexes/my%20index/bought-together/recommend/rules/rule%2F1%3Fx");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"taskID":42,"deletedAt":"2024-05-01T10:00:00Z"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+
+    let rule = client
+        .get_recommend_rule("my index", &Model::BoughtTogether, "rule/1?x")
+        .await
+        .expect("get ok");
+    assert_eq!(rule, RecommendRule::new("rule/1?x").enabled(false));
+
+    let deleted = client
+        .delete_recommend_rule("my index", &Model::BoughtTogether, "rule/1?x")
+        .await
+        .expect("delete ok");
+    assert_eq!(deleted.task_id, 42);
+
+    get.assert();
+    delete.assert();
+}
+
+#[tokio::test]
+async fn test_save_recommend_rules_sends_batch() {
+    let server = MockServer::start();
+
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/1/indexes/products/looking-similar/recommend/rules/batch")
+            .json_body(json!([
+                {
+                    "objectID": "hide-discontinued",
+                    "consequence": { "params": { "filters": "NOT status:discontinued" } }
+                },
+                {
+                    "objectID": "pin-hero",
+                    "condition": { "context": "home" },
+                    "consequence": { "promote": [{ "objectID": "hero", "position": 2 }] }
+                }
+            ]));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"taskID":7,"updatedAt":"2024-05-01T10:00:00Z"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let resp = client
+        .save_recommend_rules(
+            "products",
+            &Model::LookingSimilar,
+            &[
+                RecommendRule::new("hide-discontinued")
+                    .consequence(Consequence::new().filters("NOT status:discontinued")),
+                RecommendRule::new("pin-hero")
+                    .condition(Condition::new().context("home"))
+                    .consequence(Consequence::new().promote("hero", 2)),
+            ],
+        )
+        .await
+        .expect("request ok");
+
+    mock.assert();
+    assert_eq!(resp.task_id, 7);
+}
+
+#[tokio::test]
+async fn test_save_recommend_rules_validates_before_sending() {
+    use algolia_recommend_rs::Error;
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.any_request();
+        then.status(200).body(r#"{"taskID":1}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let err = client
+        .save_recommend_rules(
+            "products",
+            &Model::RelatedProducts,
+            &[
+                RecommendRule::new("ok"),
+                RecommendRule::new("").consequence(Consequence::new().hide(" ")),
+            ],
+        )
+        .await
+        .expect_err("should be rejected before sending");
+
+    let Error::Validation { errors } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].index, Some(1));
+    assert_eq!(errors[0].field, "objectID");
+    assert_eq!(errors[1].field, "consequence.hide.objectID");
+    assert_eq!(mock.calls(), 0);
+}
+
+#[tokio::test]
+async fn test_wait_for_task_polls_until_published() {
+    use algolia_recommend_rs::{TaskStatus, WaitForTaskOptions};
+    use std::time::Duration;
+
+    let server = MockServer::start_async().await;
+    let path = "/1/indexes/products/related-products/task/42";
+
+    let pending = server.mock(|when, then| {
+        when.method(GET).path(path);
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"status":"notPublished"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let status = client
+        .get_recommend_status("products", &Model::RelatedProducts, 42)
+        .await
+        .expect("request ok");
+    assert_eq!(status.status, TaskStatus::NotPublished);
+
+    let options = WaitForTaskOptions::new()
+        .interval(Duration::from_millis(5))
+        .max_wait(Duration::from_secs(5));
+    let waiter = tokio::spawn({
+        let client = client.clone();
+        async move {
+            client
+                .wait_for_task("products", &Model::RelatedProducts, 42, &options)
+                .await
+        }
+    });
+
+    while pending.calls_async().await < 3 {
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+    pending.delete_async().await;
+    let published = server.mock(|when, then| {
+        when.method(GET).path(path);
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"status":"published"}"#);
+    });
+
+    let status = waiter.await.unwrap().expect("task published");
+    assert_eq!(status, TaskStatus::Published);
+    published.assert();
+}
+
+#[tokio::test]
+async fn test_wait_for_task_times_out() {
+    use algolia_recommend_rs::{Error, WaitForTaskOptions};
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/1/indexes/products/bought-together/task/7");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"status":"notPublished"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let options = WaitForTaskOptions::new()
+        .interval(Duration::from_millis(10))
+        .multiplier(2.0)
+        .max_interval(Duration::from_millis(20))
+        .max_wait(Duration::from_millis(60));
+    let err = client
+        .wait_for_task("products", &Model::BoughtTogether, 7, &options)
+        .await
+        .expect_err("never published");
+
+    assert!(!err.is_retryable());
+    let Error::TaskTimeout { task_id, waited } = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(task_id, 7);
+    assert!(waited >= Duration::from_millis(60));
+    // 0, 10, 30, 50 and 60ms
+    assert!(mock.calls() >= 3);
+}
+
+#[tokio::test]
+async fn test_wait_for_task_zero_interval_does_not_spin() {
+    use algolia_recommend_rs::WaitForTaskOptions;
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/1/indexes/products/bought-together/task/7");
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"status":"notPublished"}"#);
+    });
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let options = WaitForTaskOptions::new()
+        .interval(Duration::ZERO)
+        .max_interval(Duration::ZERO)
+        .max_wait(Duration::from_millis(100));
+    client
+        .wait_for_task("products", &Model::BoughtTogether, 7, &options)
+        .await
+        .expect_err("never published");
+
+    // At most one poll per 10ms, plus the one at the deadline
+    assert!(mock.calls() <= 12, "{} polls", mock.calls());
+}
@@ -0,0 +1,264 @@
+use algolia_recommend_rs::models::Model;
+use algolia_recommend_rs::rules::{Condition, Consequence, RecommendRule};
+use algolia_recommend_rs::sync::{load_rules_dir, RuleChange, SyncOptions, SyncPlan};
+use algolia_recommend_rs::{Error, RecommendClient};
+use httpmock::prelude::*;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::path::PathBuf;
+
+const RULES_PATH: &str = "/1/indexes/products/related-products/recommend/rules";
+
+// A fresh directory per test, under the system temp dir
+fn rules_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "algolia-recommend-sync-{}-{name}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+fn mock_live_rules(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST).path(format!("{RULES_PATH}/search"));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "hits": [
+                    {
+                        "objectID": "pin-hero",
+                        "consequence": { "promote": [{ "objectID": "hero", "position": 0 }] },
+                        "enabled": true,
+                        "_metadata": { "lastUpdate": "2024-05-01T10:00:00Z" }
+                    },
+                    {
+                        "objectID": "hide-boots",
+                        "condition": { "context": "summer" },
+                        "consequence": { "hide": [{ "objectID": "boot-1" }] }
+                    },
+                    { "objectID": "legacy" }
+                ],
+                "nbHits": 3,
+                "page": 0,
+                "nbPages": 1
+            }));
+    })
+}
+
+#[test]
+fn test_load_rules_dir_reads_single_rules_and_lists() {
+    let dir = rules_dir(
+        "load",
+        &[
+            (
+                "a.json",
+                r#"{"objectID":"pin-hero","consequence":{"promote":[{"objectID":"hero","position":0}]}}"#,
+            ),
+            (
+                "b.json",
+                r#"[{"objectID":"hide-boots"},{"objectID":"new-rule","enabled":false}]"#,
+            ),
+            ("notes.txt", "not a rule"),
+        ],
+    );
+
+    let rules = load_rules_dir(&dir).expect("loads");
+    let ids: Vec<_> = rules.iter().map(|r| r.object_id.as_str()).collect();
+    assert_eq!(ids, ["pin-hero", "hide-boots", "new-rule"]);
+
+    std::fs::write(dir.join("c.json"), r#"{"objectID":"pin-hero"}"#).unwrap();
+    let err = load_rules_dir(&dir).expect_err("duplicate objectID");
+    assert!(err.to_string().contains("c.json"), "{err}");
+    assert!(err.to_string().contains("duplicate objectID \"pin-hero\""));
+
+    std::fs::write(dir.join("c.json"), r#"{"id":"oops"}"#).unwrap();
+    let err = load_rules_dir(&dir).expect_err("invalid rule");
+    assert!(err.to_string().contains("invalid rule file"), "{err}");
+}
+
+#[tokio::test]
+async fn test_sync_rules_dry_run_prints_plan_without_writing() {
+    let server = MockServer::start();
+    let search = mock_live_rules(&server);
+    let delete = server.mock(|when, then| {
+        when.method(DELETE).path(format!("{RULES_PATH}/legacy"));
+        then.status(500);
+    });
+    let batch = server.mock(|when, then| {
+        when.method(POST).path(format!("{RULES_PATH}/batch"));
+        then.status(500);
+    });
+
+    let desired = vec![
+        // Unchanged apart from what Algolia adds
+        RecommendRule::new("pin-hero").consequence(Consequence::new().promote("hero", 0)),
+        RecommendRule::new("hide-boots")
+            .condition(Condition::new().context("winter"))
+            .consequence(Consequence::new().hide("boot-1")),
+        RecommendRule::new("new-rule").enabled(false),
+    ];
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let report = client
+        .sync_rules(
+            "products",
+            &Model::RelatedProducts,
+            &desired,
+            &SyncOptions::new().dry_run(true).delete_missing(true),
+        )
+        .await
+        .expect("plan ok");
+
+    search.assert();
+    assert_eq!(delete.calls(), 0);
+    assert_eq!(batch.calls(), 0);
+    assert!(report.task_ids.is_empty());
+    assert_eq!(
+        report.plan.to_string(),
+        "Recommend rules of products (related-products):\n  \
+         ~ update hide-boots\n  \
+         - delete legacy\n  \
+         + create new-rule\n\
+         1 to create, 1 to update, 1 to delete\n"
+    );
+    assert!(matches!(
+        &report.plan.changes[0],
+        RuleChange::Update { current, .. } if current.condition == Some(Condition::new().context("summer"))
+    ));
+
+    // Live rules missing from the files are kept by default
+    let plan = client
+        .plan_rules_sync(
+            "products",
+            &Model::RelatedProducts,
+            &desired,
+            &SyncOptions::new(),
+        )
+        .await
+        .expect("plan ok");
+    assert_eq!(plan.changes.len(), 2);
+}
+
+#[tokio::test]
+async fn test_sync_rules_applies_plan() {
+    let server = MockServer::start();
+    let search = mock_live_rules(&server);
+    let batch = server.mock(|when, then| {
+        when.method(POST)
+            .path(format!("{RULES_PATH}/batch"))
+            .json_body(json!([
+                { "objectID": "hide-boots", "consequence": { "hide": [{ "objectID": "boot-2" }] } },
+                { "objectID": "new-rule", "description": "From git" }
+            ]));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"taskID":11,"updatedAt":"2024-05-01T10:00:00Z"}"#);
+    });
+    let delete = server.mock(|when, then| {
+        when.method(DELETE).path(format!("{RULES_PATH}/legacy"));
+        then.status(200)
+            .header("content-type", "application/json")
+            .body(r#"{"taskID":12,"deletedAt":"2024-05-01T10:00:00Z"}"#);
+    });
+
+    let dir = rules_dir(
+        "apply",
+        &[(
+            "rules.json",
+            r#"[
+                    {"objectID":"pin-hero","consequence":{"promote":[{"objectID":"hero","position":0}]}},
+                    {"objectID":"hide-boots","consequence":{"hide":[{"objectID":"boot-2"}]}},
+                    {"objectID":"new-rule","description":"From git"}
+                ]"#,
+        )],
+    );
+    let desired = load_rules_dir(&dir).expect("loads");
+
+    let client = RecommendClient::with_base_url("APPID", "KEY", server.base_url());
+    let report = client
+        .sync_rules(
+            "products",
+            &Model::RelatedProducts,
+            &desired,
+            &SyncOptions::new().delete_missing(true),
+        )
+        .await
+        .expect("sync ok");
+
+    search.assert();
+    batch.assert();
+    delete.assert();
+    assert_eq!(report.task_ids, [11, 12]);
+
+    // Nothing left to do once live matches the files
+    let plan = SyncPlan::new(
+        "products",
+        Model::RelatedProducts,
+        &desired,
+        &desired,
+        &SyncOptions::default(),
+    )
+    .expect("plan ok");
+    assert!(plan.is_empty());
+    assert!(plan.to_string().contains("no changes"));
+}
+
+#[test]
+fn test_sync_plan_rejects_duplicate_object_ids() {
+    let desired = [
+        RecommendRule::new("pin-hero"),
+        RecommendRule::new("hide-boots"),
+        RecommendRule::new("pin-hero").enabled(false),
+    ];
+
+    let err = SyncPlan::new(
+        "products",
+        Model::RelatedProducts,
+        &desired,
+        &[],
+        &SyncOptions::default(),
+    )
+    .expect_err("duplicate objectID");
+    let Error::Validation { errors } = err else {
+        panic!("expected a validation error, got {err:?}");
+    };
+    assert_eq!(errors[0].index, Some(2));
+    assert_eq!(errors[0].field, "objectID");
+    assert_eq!(errors[0].message, "duplicate objectID \"pin-hero\"");
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_load_rules_dir_reads_yaml() {
+    let dir = rules_dir(
+        "yaml",
+        &[(
+            "rules.yaml",
+            "- objectID: pin-hero\n  consequence:\n    promote:\n      - objectID: hero\n        position: 0\n",
+        )],
+    );
+
+    let rules = load_rules_dir(&dir).expect("loads");
+    assert_eq!(
+        rules,
+        [RecommendRule::new("pin-hero").consequence(Consequence::new().promote("hero", 0))]
+    );
+}
+
+#[cfg(not(feature = "yaml"))]
+#[test]
+fn test_load_rules_dir_rejects_yaml_without_the_feature() {
+    let dir = rules_dir("no-yaml", &[("rules.yml", "- objectID: pin-hero\n")]);
+
+    let err = load_rules_dir(&dir).expect_err("yaml is not skipped");
+    let Error::RuleFile { path, message } = err else {
+        panic!("expected a rule file error, got {err:?}");
+    };
+    assert_eq!(path, dir.join("rules.yml"));
+    assert_eq!(message, "yaml feature not enabled");
+}